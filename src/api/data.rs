@@ -1,6 +1,5 @@
 //defining structs here for convenience and to clear up api.rs
 
-use ratatui::widgets::ListItem;
 use reqwest::blocking::Client;
 use serde_json::Value;
 
@@ -15,6 +14,27 @@ fn get_length(list: &serde_json::Value) -> usize {
     the_length.unwrap().len()
 }
 
+//Permission bitfields come as strings
+fn permission_bits(value: &Value) -> u64 {
+    value
+        .as_str()
+        .and_then(|v| v.parse().ok())
+        .or(value.as_u64())
+        .unwrap_or(0)
+}
+
+//Array of ids, empty if it's missing
+fn string_list(list: &Value) -> Vec<String> {
+    match list.as_array() {
+        Some(v) => v
+            .iter()
+            .filter_map(|item| item.as_str())
+            .map(|item| item.to_string())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Connection {
     pub auth: (String, String),
@@ -37,23 +57,177 @@ pub struct GatewayResponse {
     pub operation: String,
     pub message: Msg,
     pub guilds: Vec<Guild>,
+    pub dms: Vec<Channel>,
+    pub presences: Vec<Presence>,
+    pub read_states: Vec<ReadState>,
+    pub guild_settings: Vec<GuildSettings>,
+    pub member_list: Option<MemberListUpdate>,
+    pub members_chunk: Option<MembersChunk>,
+    pub reaction: Option<ReactionUpdate>,
+    //Who we are logged in as, only filled in on READY
+    pub user: User,
+    //Also READY only, interactions have to name the session they come from
+    pub session_id: String,
+    //THREAD_CREATE, THREAD_UPDATE and THREAD_DELETE
+    pub channel: Option<Channel>,
+    pub thread_sync: Option<ThreadListSync>,
+    pub thread_members: Option<ThreadMembersUpdate>,
+    pub voice_state: Option<VoiceState>,
+    pub voice_sync: Option<VoiceStateSync>,
 }
 
 impl GatewayResponse {
+    //Every event only fills in what it needs, the rest stays empty
+    fn empty(operation: &str) -> GatewayResponse {
+        GatewayResponse {
+            operation: operation.to_string(),
+            message: Msg::new(),
+            guilds: Vec::new(),
+            dms: Vec::new(),
+            presences: Vec::new(),
+            read_states: Vec::new(),
+            guild_settings: Vec::new(),
+            member_list: None,
+            members_chunk: None,
+            reaction: None,
+            user: User::new(),
+            session_id: String::new(),
+            channel: None,
+            thread_sync: None,
+            thread_members: None,
+            voice_state: None,
+            voice_sync: None,
+        }
+    }
+
     pub fn msg_create(message: Msg) -> GatewayResponse {
         GatewayResponse {
-            operation: "MESSAGE_CREATE".to_string(),
             message,
-            guilds: Vec::new(),
+            ..GatewayResponse::empty("MESSAGE_CREATE")
+        }
+    }
+
+    //Edits, and bots filling in a deferred reply
+    pub fn msg_update(message: Msg) -> GatewayResponse {
+        GatewayResponse {
+            message,
+            ..GatewayResponse::empty("MESSAGE_UPDATE")
         }
     }
 
+    //The bot never answered a command we sent
+    pub fn interaction_failure() -> GatewayResponse {
+        GatewayResponse::empty("INTERACTION_FAILURE")
+    }
+
+    //The gateway thread lost the connection and stopped
+    pub fn disconnected() -> GatewayResponse {
+        GatewayResponse::empty("DISCONNECTED")
+    }
+
     //Send initial data like guilds
-    pub fn ready(guilds: Vec<Guild>) -> GatewayResponse {
+    pub fn ready(
+        guilds: Vec<Guild>,
+        dms: Vec<Channel>,
+        presences: Vec<Presence>,
+        read_states: Vec<ReadState>,
+        guild_settings: Vec<GuildSettings>,
+        user: User,
+        session_id: String,
+    ) -> GatewayResponse {
         GatewayResponse {
-            operation: "READY".to_string(),
-            message: Msg::new(),
             guilds,
+            dms,
+            presences,
+            read_states,
+            guild_settings,
+            user,
+            session_id,
+            ..GatewayResponse::empty("READY")
+        }
+    }
+
+    pub fn presence_update(presence: Presence) -> GatewayResponse {
+        GatewayResponse {
+            presences: vec![presence],
+            ..GatewayResponse::empty("PRESENCE_UPDATE")
+        }
+    }
+
+    //A channel got read, possibly from another client
+    pub fn message_ack(read_state: ReadState) -> GatewayResponse {
+        GatewayResponse {
+            read_states: vec![read_state],
+            ..GatewayResponse::empty("MESSAGE_ACK")
+        }
+    }
+
+    //Mute/notification settings changed, possibly from another client
+    pub fn guild_settings_update(settings: GuildSettings) -> GatewayResponse {
+        GatewayResponse {
+            guild_settings: vec![settings],
+            ..GatewayResponse::empty("USER_GUILD_SETTINGS_UPDATE")
+        }
+    }
+
+    pub fn member_list_update(update: MemberListUpdate) -> GatewayResponse {
+        GatewayResponse {
+            member_list: Some(update),
+            ..GatewayResponse::empty("GUILD_MEMBER_LIST_UPDATE")
+        }
+    }
+
+    pub fn members_chunk(chunk: MembersChunk) -> GatewayResponse {
+        GatewayResponse {
+            members_chunk: Some(chunk),
+            ..GatewayResponse::empty("GUILD_MEMBERS_CHUNK")
+        }
+    }
+
+    //operation is THREAD_CREATE, THREAD_UPDATE or THREAD_DELETE
+    pub fn thread(operation: &str, thread: Channel) -> GatewayResponse {
+        GatewayResponse {
+            channel: Some(thread),
+            ..GatewayResponse::empty(operation)
+        }
+    }
+
+    pub fn thread_list_sync(sync: ThreadListSync) -> GatewayResponse {
+        GatewayResponse {
+            thread_sync: Some(sync),
+            ..GatewayResponse::empty("THREAD_LIST_SYNC")
+        }
+    }
+
+    //Both THREAD_MEMBER_UPDATE and THREAD_MEMBERS_UPDATE
+    pub fn thread_members(update: ThreadMembersUpdate) -> GatewayResponse {
+        GatewayResponse {
+            thread_members: Some(update),
+            ..GatewayResponse::empty("THREAD_MEMBERS")
+        }
+    }
+
+    //Someone joined, left, moved or (un)muted
+    pub fn voice_state_update(state: VoiceState) -> GatewayResponse {
+        GatewayResponse {
+            voice_state: Some(state),
+            ..GatewayResponse::empty("VOICE_STATE_UPDATE")
+        }
+    }
+
+    //Everyone in voice when GUILD_CREATE comes in, the rest of the guild isn't used
+    pub fn guild_voice_states(sync: VoiceStateSync) -> GatewayResponse {
+        GatewayResponse {
+            voice_sync: Some(sync),
+            ..GatewayResponse::empty("GUILD_VOICE_STATES")
+        }
+    }
+
+    //Both MESSAGE_REACTION_ADD and MESSAGE_REACTION_REMOVE
+    pub fn reaction_update(update: ReactionUpdate) -> GatewayResponse {
+        GatewayResponse {
+            reaction: Some(update),
+            ..GatewayResponse::empty("MESSAGE_REACTION")
         }
     }
 }
@@ -63,6 +237,11 @@ pub struct User {
     pub id: String,
     pub name: String,
     pub discriminator: String,
+    //Display name, falls back to the username when unset
+    pub global_name: Option<String>,
+    //0 none, 1 Nitro Classic, 2 Nitro, 3 Nitro Basic. Only known for ourselves
+    pub premium_type: i64,
+    pub bot: bool,
 }
 
 impl User {
@@ -71,17 +250,34 @@ impl User {
             id: "111".to_string(),
             name: "Dev".to_string(),
             discriminator: "0001".to_string(),
+            global_name: None,
+            premium_type: 0,
+            bot: false,
         }
     }
     pub fn from(author: &Value) -> User {
         let id = author["id"].as_str().unwrap().to_string();
         let name = author["username"].as_str().unwrap().to_string();
-        let discriminator = author["discriminator"].as_str().unwrap().to_string();
+        //Users on the new username system don't always send one
+        let discriminator = author["discriminator"].as_str().unwrap_or("0").to_string();
+        let global_name = author["global_name"].as_str().map(|v| v.to_string());
+        let premium_type = author["premium_type"].as_i64().unwrap_or(0);
+        let bot = author["bot"].as_bool().unwrap_or(false);
 
         User {
             id,
             name,
             discriminator,
+            global_name,
+            premium_type,
+            bot,
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        match &self.global_name {
+            Some(v) => v,
+            None => &self.name,
         }
     }
 }
@@ -91,30 +287,28 @@ pub struct Guild {
     pub id: String,
     pub name: String,
     pub channels: Vec<Channel>,
+    pub roles: Vec<Role>,
+    pub emojis: Vec<GuildEmoji>,
+    //Active forum posts, they only go in the sidebar once opened
+    pub posts: Vec<Channel>,
+    //Who is connected to which voice channel
+    pub voice_states: Vec<VoiceState>,
+    //Role ids we have here, for role pings and permissions
+    pub own_roles: Vec<String>,
+    //Can do anything, whatever the roles say
+    pub owner_id: String,
+    //What our notification settings fall back to when they're left alone
+    pub default_notifications: NotificationLevel,
 }
 
 impl Guild {
     pub fn from_list(event: &Value) -> Vec<Guild> {
         //VERY UGLY + WRAPPER DUPE. Fix eventually
-        let guild_vc = String::from("2");
         let category = String::from("4");
-        let announcement_thread = String::from("10");
-        let public_thread = String::from("11");
-        let private_thread = String::from("12");
-        let guild_stage_vc = String::from("13");
         let guild_directory = String::from("14");
-        let guild_forum = String::from("15");
-
-        let ignored_channels = Vec::from([
-            guild_vc,
-            category,
-            announcement_thread,
-            public_thread,
-            private_thread,
-            guild_stage_vc,
-            guild_directory,
-            guild_forum,
-        ]);
+
+        //Threads come separately, they get nested under their channel below
+        let ignored_channels = Vec::from([category, guild_directory]);
 
         let guilds = &event["guilds"];
         let length = get_length(guilds);
@@ -126,7 +320,9 @@ impl Guild {
             let channel_length = get_length(&guilds[i]["channels"]);
             let mut channels = Vec::new();
             for j in 0..channel_length {
-                let channel = Channel::from(&guilds[i]["channels"][j]);
+                let mut channel = Channel::from(&guilds[i]["channels"][j]);
+                //READY leaves the guild id out of nested channels
+                channel.guild_id = id.clone();
 
                 if ignored_channels.contains(&channel.channel_type) {
                     continue;
@@ -135,7 +331,39 @@ impl Guild {
                 }
             }
 
-            let guild = Guild { id, name, channels };
+            let roles = Role::from_list(&guilds[i]["roles"]);
+            let emojis = GuildEmoji::from_list(&guilds[i]["emojis"]);
+            let voice_states = VoiceState::from_list(&guilds[i]["voice_states"], &id);
+            //merged_members lines up with guilds, each list only has our own member
+            let own_roles = string_list(&event["merged_members"][i][0]["roles"]);
+            let owner_id = guilds[i]["owner_id"]
+                .as_str()
+                .or(guilds[i]["properties"]["owner_id"].as_str())
+                .unwrap_or_default()
+                .to_string();
+            let default_notifications = guilds[i]["default_message_notifications"]
+                .as_i64()
+                .or(guilds[i]["properties"]["default_message_notifications"].as_i64())
+                .unwrap_or(1);
+
+            let mut guild = Guild {
+                id,
+                name,
+                channels,
+                roles,
+                emojis,
+                posts: Vec::new(),
+                voice_states,
+                own_roles,
+                owner_id,
+                default_notifications: NotificationLevel::from(default_notifications),
+            };
+            //Only the active ones, archived threads have to be asked for
+            for thread in guilds[i]["threads"].as_array().unwrap_or(&Vec::new()) {
+                let mut thread = Channel::from(thread);
+                thread.guild_id = guild.id.clone();
+                guild.insert_thread(thread);
+            }
             guild_list.push(guild);
         }
 
@@ -151,63 +379,1868 @@ impl Guild {
             id,
             name,
             channels: Vec::new(),
+            roles: Vec::new(),
+            emojis: Vec::new(),
+            posts: Vec::new(),
+            voice_states: Vec::new(),
+            own_roles: Vec::new(),
+            owner_id: String::new(),
+            default_notifications: NotificationLevel::Mentions,
+        }
+    }
+
+    pub fn get_role(&self, id: &str) -> Option<&Role> {
+        self.roles.iter().find(|role| role.id == id)
+    }
+
+    //What we're allowed to do in a channel, threads go by their parent
+    //@everyone and our roles, then the channel's overwrites: @everyone, roles, us
+    pub fn permissions(&self, channel: &Channel, user_id: &str) -> u64 {
+        if self.owner_id == user_id {
+            return u64::MAX;
+        }
+
+        let mut permissions = self
+            .roles
+            .iter()
+            .filter(|role| role.id == self.id || self.own_roles.contains(&role.id))
+            .fold(0, |permissions, role| permissions | role.permissions);
+        if permissions & ADMINISTRATOR != 0 {
+            return u64::MAX;
+        }
+
+        let channel = match channel.is_thread() {
+            true => channel
+                .parent_id
+                .as_ref()
+                .and_then(|id| self.channels.iter().find(|v| v.id == *id))
+                .unwrap_or(channel),
+            false => channel,
+        };
+        let overwrites = &channel.overwrites;
+
+        if let Some(everyone) = overwrites.iter().find(|v| v.id == self.id) {
+            permissions = (permissions & !everyone.deny) | everyone.allow;
+        }
+        let (allow, deny) = overwrites
+            .iter()
+            .filter(|v| !v.member && self.own_roles.contains(&v.id))
+            .fold((0, 0), |(allow, deny), v| (allow | v.allow, deny | v.deny));
+        permissions = (permissions & !deny) | allow;
+        if let Some(ours) = overwrites.iter().find(|v| v.member && v.id == user_id) {
+            permissions = (permissions & !ours.deny) | ours.allow;
+        }
+
+        permissions
+    }
+
+    //Replaces the user's old state, disconnecting drops it
+    pub fn update_voice_state(&mut self, state: VoiceState) {
+        self.voice_states.retain(|v| v.user_id != state.user_id);
+        if state.channel_id.is_some() {
+            self.voice_states.push(state);
+        }
+    }
+
+    pub fn voice_states_in(&self, channel_id: &str) -> Vec<&VoiceState> {
+        self.voice_states
+            .iter()
+            .filter(|state| state.channel_id.as_deref() == Some(channel_id))
+            .collect()
+    }
+
+    //Puts a thread after its channel and that channel's other threads, replacing an old copy
+    //Forum posts are kept aside instead, unless they're already in the sidebar
+    //False if the channel isn't in the list
+    pub fn insert_thread(&mut self, thread: Channel) -> bool {
+        let parent = self
+            .channels
+            .iter()
+            .find(|channel| Some(&channel.id) == thread.parent_id.as_ref());
+        match parent {
+            None => return false,
+            Some(parent) if parent.is_forum() => {
+                self.posts.retain(|post| post.id != thread.id);
+                self.posts.push(thread.clone());
+                if let Some(shown) = self.channels.iter_mut().find(|v| v.id == thread.id) {
+                    *shown = thread;
+                }
+                return true;
+            }
+            Some(_) => (),
+        }
+
+        self.show_thread(thread)
+    }
+
+    //Nests a thread in the sidebar, forum posts too
+    pub fn show_thread(&mut self, thread: Channel) -> bool {
+        let parent = match self
+            .channels
+            .iter()
+            .position(|channel| Some(&channel.id) == thread.parent_id.as_ref())
+        {
+            Some(v) => v,
+            None => return false,
+        };
+        //Threads always come after their channel, so this leaves parent where it is
+        self.channels.retain(|channel| channel.id != thread.id);
+
+        let mut index = parent + 1;
+        while index < self.channels.len() && self.channels[index].is_thread() {
+            index += 1;
         }
+        self.channels.insert(index, thread);
+        true
+    }
+
+    pub fn remove_channel(&mut self, channel_id: &str) {
+        self.channels.retain(|channel| channel.id != channel_id);
+        self.posts.retain(|post| post.id != channel_id);
     }
 }
 
+//Permission bits we check before offering something
+pub const ADMINISTRATOR: u64 = 1 << 3;
+pub const SEND_MESSAGES: u64 = 1 << 11;
+pub const MANAGE_MESSAGES: u64 = 1 << 13;
+pub const MANAGE_WEBHOOKS: u64 = 1 << 29;
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub struct Channel {
+pub struct Role {
     pub id: String,
     pub name: String,
-    pub channel_type: String,
+    //0xRRGGBB, 0 means no color
+    pub color: u32,
+    //Higher goes first
+    pub position: i64,
+    //Shown separately in the member list
+    pub hoist: bool,
+    pub permissions: u64,
 }
 
-impl Channel {
-    pub fn from(event: &Value) -> Channel {
+impl Role {
+    pub fn from(event: &Value) -> Role {
         let id = event["id"].as_str().unwrap().to_string();
-        let name = event["name"].as_str().unwrap().to_string();
-        let channel_type = event["type"].as_i64().unwrap().to_string();
+        let name = event["name"].as_str().unwrap_or_default().to_string();
+        let color = event["color"].as_u64().unwrap_or(0) as u32;
+        let position = event["position"].as_i64().unwrap_or(0);
+        let hoist = event["hoist"].as_bool().unwrap_or(false);
+        //Sent as a string, it doesn't fit in a js number
+        let permissions = permission_bits(&event["permissions"]);
 
-        Channel {
+        Role {
             id,
             name,
-            channel_type,
+            color,
+            position,
+            hoist,
+            permissions,
+        }
+    }
+
+    pub fn from_list(roles: &Value) -> Vec<Role> {
+        match roles.as_array() {
+            Some(v) => v.iter().map(Role::from).collect(),
+            None => Vec::new(),
         }
     }
 }
 
+//Custom emoji uploaded to a guild
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
-pub struct Msg {
+pub struct GuildEmoji {
     pub id: String,
-    pub channel_id: String,
+    pub name: String,
+    pub animated: bool,
+}
+
+impl GuildEmoji {
+    pub fn from(event: &Value) -> GuildEmoji {
+        let id = event["id"].as_str().unwrap().to_string();
+        let name = event["name"].as_str().unwrap_or_default().to_string();
+        let animated = event["animated"].as_bool().unwrap_or(false);
+
+        GuildEmoji { id, name, animated }
+    }
+
+    pub fn from_list(emojis: &Value) -> Vec<GuildEmoji> {
+        match emojis.as_array() {
+            Some(v) => v.iter().map(GuildEmoji::from).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    //What goes in message content, <:name:id> or <a:name:id>
+    pub fn markup(&self) -> String {
+        match self.animated {
+            true => format!("<a:{}:{}>", self.name, self.id),
+            false => format!("<:{}:{}>", self.name, self.id),
+        }
+    }
+}
+
+//A user as seen from inside a guild
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Member {
     pub user: User,
-    pub content: String,
+    pub nick: Option<String>,
+    //Role ids
+    pub roles: Vec<String>,
+    pub status: String,
 }
 
-impl Msg {
-    pub fn new() -> Msg {
-        Msg {
-            id: "222".to_string(),
-            channel_id: "333".to_string(),
-            user: User::new(),
-            content: "Unable to open a channel without proper permission".to_string(),
+impl Member {
+    pub fn from(event: &Value) -> Member {
+        let user = User::from(&event["user"]);
+        Member::with_user(event, user)
+    }
+
+    //Members attached to messages leave the user out, it's the author
+    pub fn with_user(event: &Value, user: User) -> Member {
+        let nick = event["nick"].as_str().map(|v| v.to_string());
+        let roles = string_list(&event["roles"]);
+        let status = event["presence"]["status"]
+            .as_str()
+            .unwrap_or("offline")
+            .to_string();
+
+        Member {
+            user,
+            nick,
+            roles,
+            status,
         }
     }
-    //Might not work for every event in mind ??
-    pub fn from(event: &Value) -> Msg {
-        let id = event["id"].as_str().unwrap().to_string();
-        let channel_id = event["channel_id"].as_str().unwrap().to_string();
 
-        let author = &event["author"];
-        let user = User::from(author);
+    //Guild nickname > display name > username
+    pub fn display_name(&self) -> &str {
+        match &self.nick {
+            Some(v) => v,
+            None => self.user.display_name(),
+        }
+    }
 
-        let content = event["content"].as_str().unwrap().to_string();
+    //Color of the highest role that has one
+    pub fn color(&self, guild: &Guild) -> Option<u32> {
+        self.roles
+            .iter()
+            .filter_map(|id| guild.get_role(id))
+            .filter(|role| role.color != 0)
+            .max_by_key(|role| role.position)
+            .map(|role| role.color)
+    }
+}
 
-        Msg {
+//Answer to a request guild members (op 8)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MembersChunk {
+    pub guild_id: String,
+    pub members: Vec<Member>,
+}
+
+impl MembersChunk {
+    pub fn from(event: &Value) -> MembersChunk {
+        let guild_id = event["guild_id"].as_str().unwrap().to_string();
+        let members = match event["members"].as_array() {
+            Some(v) => v.iter().map(Member::from).collect(),
+            None => Vec::new(),
+        };
+
+        MembersChunk { guild_id, members }
+    }
+}
+
+//Rows of the member list, groups are role ids or "online"/"offline"
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum MemberListItem {
+    Group { id: String, count: i64 },
+    Member(Member),
+}
+
+impl MemberListItem {
+    pub fn from(event: &Value) -> MemberListItem {
+        if event["group"].is_object() {
+            let group = &event["group"];
+            MemberListItem::Group {
+                id: group["id"].as_str().unwrap_or_default().to_string(),
+                count: group["count"].as_i64().unwrap_or(0),
+            }
+        } else {
+            MemberListItem::Member(Member::from(&event["member"]))
+        }
+    }
+}
+
+//The ops of GUILD_MEMBER_LIST_UPDATE, indexes are rows of the whole list
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum MemberListOp {
+    Sync {
+        start: usize,
+        end: usize,
+        items: Vec<MemberListItem>,
+    },
+    Insert {
+        index: usize,
+        item: MemberListItem,
+    },
+    Update {
+        index: usize,
+        item: MemberListItem,
+    },
+    Delete {
+        index: usize,
+    },
+    Invalidate,
+}
+
+impl MemberListOp {
+    pub fn from(event: &Value) -> Option<MemberListOp> {
+        let index = event["index"].as_u64().unwrap_or(0) as usize;
+        let start = event["range"][0].as_u64().unwrap_or(0) as usize;
+        let end = event["range"][1].as_u64().unwrap_or(0) as usize;
+
+        let op = match event["op"].as_str()? {
+            "SYNC" => MemberListOp::Sync {
+                start,
+                end,
+                items: match event["items"].as_array() {
+                    Some(v) => v.iter().map(MemberListItem::from).collect(),
+                    None => Vec::new(),
+                },
+            },
+            "INSERT" => MemberListOp::Insert {
+                index,
+                item: MemberListItem::from(&event["item"]),
+            },
+            "UPDATE" => MemberListOp::Update {
+                index,
+                item: MemberListItem::from(&event["item"]),
+            },
+            "DELETE" => MemberListOp::Delete { index },
+            "INVALIDATE" => MemberListOp::Invalidate,
+            _ => return None,
+        };
+
+        Some(op)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MemberListUpdate {
+    pub guild_id: String,
+    //Which list, channels with the same permissions share one
+    pub id: String,
+    pub ops: Vec<MemberListOp>,
+}
+
+impl MemberListUpdate {
+    pub fn from(event: &Value) -> MemberListUpdate {
+        let guild_id = event["guild_id"].as_str().unwrap().to_string();
+        let id = event["id"].as_str().unwrap_or_default().to_string();
+        let ops = match event["ops"].as_array() {
+            Some(v) => v.iter().filter_map(MemberListOp::from).collect(),
+            None => Vec::new(),
+        };
+
+        MemberListUpdate { guild_id, id, ops }
+    }
+
+    //A new subscription always starts with one
+    pub fn has_sync(&self) -> bool {
+        self.ops
+            .iter()
+            .any(|op| matches!(op, MemberListOp::Sync { .. }))
+    }
+
+    //Members the ops bring in, the rest of the list hasn't changed
+    pub fn members(&self) -> Vec<&Member> {
+        let mut members = Vec::new();
+        for op in &self.ops {
+            let items = match op {
+                MemberListOp::Sync { items, .. } => items.iter().collect(),
+                MemberListOp::Insert { item, .. } | MemberListOp::Update { item, .. } => {
+                    vec![item]
+                }
+                _ => Vec::new(),
+            };
+            for item in items {
+                if let MemberListItem::Member(member) = item {
+                    members.push(member);
+                }
+            }
+        }
+        members
+    }
+
+    //Applies the ops to our copy of the list
+    pub fn apply(&self, list: &mut Vec<MemberListItem>) {
+        for op in &self.ops {
+            match op {
+                MemberListOp::Sync { start, end, items } => {
+                    let start = (*start).min(list.len());
+                    let end = (*end + 1).min(list.len());
+                    list.splice(start..end, items.iter().cloned());
+                }
+                MemberListOp::Insert { index, item } => {
+                    list.insert((*index).min(list.len()), item.clone());
+                }
+                MemberListOp::Update { index, item } => {
+                    if let Some(row) = list.get_mut(*index) {
+                        *row = item.clone();
+                    }
+                }
+                MemberListOp::Delete { index } => {
+                    if *index < list.len() {
+                        list.remove(*index);
+                    }
+                }
+                //Rows stay where they are until the next SYNC
+                //removing them would shift everything below
+                MemberListOp::Invalidate => (),
+            }
+        }
+    }
+}
+
+//Channels are compared by id only so stale copies still find their messages
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pub id: String,
+    pub name: String,
+    pub channel_type: String,
+    //Only as fresh as when the channel was fetched, App keeps the live one
+    pub last_message_id: String,
+    //"@me" for DMs
+    pub guild_id: String,
+    //The channel a thread lives in, or a channel's category
+    pub parent_id: Option<String>,
+    //Only for threads
+    pub thread: Option<ThreadInfo>,
+    //Tags a forum offers, and the ones a post has (ids)
+    pub available_tags: Vec<ForumTag>,
+    pub applied_tags: Vec<String>,
+    //Permission changes for roles and members in this channel
+    pub overwrites: Vec<Overwrite>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Overwrite {
+    //A role id, the guild id for @everyone, or a user id when member is set
+    pub id: String,
+    pub member: bool,
+    pub allow: u64,
+    pub deny: u64,
+}
+
+impl Overwrite {
+    pub fn from_list(overwrites: &Value) -> Vec<Overwrite> {
+        match overwrites.as_array() {
+            Some(v) => v
+                .iter()
+                .map(|overwrite| Overwrite {
+                    id: overwrite["id"].as_str().unwrap_or_default().to_string(),
+                    member: overwrite["type"].as_i64() == Some(1),
+                    allow: permission_bits(&overwrite["allow"]),
+                    deny: permission_bits(&overwrite["deny"]),
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ForumTag {
+    pub id: String,
+    pub name: String,
+    //Unicode only, custom ones can't be drawn
+    pub emoji: Option<String>,
+}
+
+impl ForumTag {
+    pub fn from_list(tags: &Value) -> Vec<ForumTag> {
+        let tags = match tags.as_array() {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        tags.iter()
+            .map(|tag| ForumTag {
+                id: tag["id"].as_str().unwrap_or_default().to_string(),
+                name: tag["name"].as_str().unwrap_or_default().to_string(),
+                emoji: tag["emoji_name"].as_str().map(|v| v.to_string()),
+            })
+            .collect()
+    }
+
+    pub fn display(&self) -> String {
+        match &self.emoji {
+            Some(emoji) => format!("{} {}", emoji, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ThreadInfo {
+    pub message_count: u64,
+    pub archived: bool,
+    pub locked: bool,
+    //Whether we're a member, only known from READY, THREAD_LIST_SYNC and member updates
+    pub joined: bool,
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Channel {}
+
+impl std::hash::Hash for Channel {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl Channel {
+    pub fn from(event: &Value) -> Channel {
+        let id = event["id"].as_str().unwrap().to_string();
+        //DMs have no name, so they get named after whoever is in them
+        let name = match event["name"].as_str() {
+            Some(v) => v.to_string(),
+            None => Channel::recipient_names(&event["recipients"]),
+        };
+        let channel_type = event["type"].as_i64().unwrap().to_string();
+        let last_message_id = event["last_message_id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let guild_id = event["guild_id"].as_str().unwrap_or("@me").to_string();
+        let parent_id = event["parent_id"].as_str().map(|v| v.to_string());
+        let thread = match channel_type.as_str() {
+            "10" | "11" | "12" => Some(ThreadInfo {
+                message_count: event["message_count"].as_u64().unwrap_or(0),
+                archived: event["thread_metadata"]["archived"]
+                    .as_bool()
+                    .unwrap_or(false),
+                locked: event["thread_metadata"]["locked"]
+                    .as_bool()
+                    .unwrap_or(false),
+                joined: event["member"].is_object(),
+            }),
+            _ => None,
+        };
+        let available_tags = ForumTag::from_list(&event["available_tags"]);
+        let applied_tags = string_list(&event["applied_tags"]);
+        let overwrites = Overwrite::from_list(&event["permission_overwrites"]);
+
+        Channel {
             id,
-            channel_id,
-            user,
-            content,
+            name,
+            channel_type,
+            last_message_id,
+            guild_id,
+            parent_id,
+            thread,
+            available_tags,
+            applied_tags,
+            overwrites,
         }
     }
+
+    pub fn is_thread(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    //Voice and stage channels, their text chat works like any other channel
+    pub fn is_voice(&self) -> bool {
+        self.channel_type == "2" || self.channel_type == "13"
+    }
+
+    //Other servers can follow these, their messages get published there
+    pub fn is_announcement(&self) -> bool {
+        self.channel_type == "5"
+    }
+
+    //Media channels work the same, just with pictures
+    pub fn is_forum(&self) -> bool {
+        self.channel_type == "15" || self.channel_type == "16"
+    }
+
+    //DMs and group DMs from READY
+    pub fn from_private_list(event: &Value) -> Vec<Channel> {
+        match event["private_channels"].as_array() {
+            Some(v) => v.iter().map(Channel::from).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn recipient_names(recipients: &Value) -> String {
+        let names: Vec<&str> = match recipients.as_array() {
+            Some(v) => v.iter().filter_map(|r| r["username"].as_str()).collect(),
+            None => Vec::new(),
+        };
+
+        names.join(", ")
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RelationshipKind {
+    Friend,
+    Blocked,
+    Incoming,
+    Outgoing,
+    //Types we don't know or show, like implicit relationships
+    Other,
+}
+
+impl RelationshipKind {
+    //Numbers are the relationship types discord uses
+    pub fn from(kind: i64) -> RelationshipKind {
+        match kind {
+            1 => RelationshipKind::Friend,
+            2 => RelationshipKind::Blocked,
+            3 => RelationshipKind::Incoming,
+            4 => RelationshipKind::Outgoing,
+            _ => RelationshipKind::Other,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            RelationshipKind::Friend => "Friend",
+            RelationshipKind::Blocked => "Blocked",
+            RelationshipKind::Incoming => "Incoming request",
+            RelationshipKind::Outgoing => "Outgoing request",
+            RelationshipKind::Other => "Other",
+        }
+    }
+}
+
+//Entry from /users/@me/relationships
+//The user is nested, unlike message authors
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Relationship {
+    pub user: User,
+    pub kind: RelationshipKind,
+    pub status: String,
+}
+
+impl Relationship {
+    pub fn from(event: &Value) -> Relationship {
+        let user = User::from(&event["user"]);
+        let kind = RelationshipKind::from(event["type"].as_i64().unwrap_or(1));
+
+        Relationship {
+            user,
+            kind,
+            status: "offline".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Presence {
+    pub user_id: String,
+    pub status: String,
+}
+
+impl Presence {
+    pub fn from(event: &Value) -> Presence {
+        let user_id = event["user"]["id"].as_str().unwrap().to_string();
+        let status = event["status"].as_str().unwrap_or("offline").to_string();
+
+        Presence { user_id, status }
+    }
+
+    //Depending on identify capabilities, READY has them under "presences" or "merged_presences"
+    pub fn from_list(event: &Value) -> Vec<Presence> {
+        let mut presences = Vec::new();
+
+        let lists = [&event["presences"], &event["merged_presences"]["friends"]];
+        for list in lists {
+            if let Some(v) = list.as_array() {
+                for presence in v {
+                    //Merged presences only carry the id
+                    let user_id = match presence["user_id"].as_str() {
+                        Some(id) => id.to_string(),
+                        None => presence["user"]["id"]
+                            .as_str()
+                            .unwrap_or_default()
+                            .to_string(),
+                    };
+                    let status = presence["status"].as_str().unwrap_or("offline").to_string();
+
+                    presences.push(Presence { user_id, status });
+                }
+            }
+        }
+
+        presences
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ReadState {
+    pub channel_id: String,
+    pub last_read_id: String,
+    pub mention_count: i64,
+}
+
+impl ReadState {
+    //Works for both READY entries and MESSAGE_ACK events
+    pub fn from(event: &Value) -> ReadState {
+        let channel_id = match event["channel_id"].as_str() {
+            Some(v) => v.to_string(),
+            None => event["id"].as_str().unwrap().to_string(),
+        };
+        let last_read_id = match event["message_id"].as_str() {
+            Some(v) => v.to_string(),
+            None => event["last_message_id"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        };
+        let mention_count = event["mention_count"].as_i64().unwrap_or(0);
+
+        ReadState {
+            channel_id,
+            last_read_id,
+            mention_count,
+        }
+    }
+
+    //Older gateway versions send a plain list, newer ones wrap it in "entries"
+    pub fn from_list(event: &Value) -> Vec<ReadState> {
+        let read_state = &event["read_state"];
+        let entries = match read_state.as_array() {
+            Some(v) => v,
+            None => match read_state["entries"].as_array() {
+                Some(v) => v,
+                None => return Vec::new(),
+            },
+        };
+
+        entries
+            .iter()
+            //Some entries are for other things than channels and have no id
+            .filter(|entry| entry["id"].is_string())
+            .map(ReadState::from)
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NotificationLevel {
+    All,
+    Mentions,
+    Nothing,
+    //Channel overrides that follow the guild, guilds that follow the server default
+    Inherit,
+}
+
+impl NotificationLevel {
+    pub fn from(level: i64) -> NotificationLevel {
+        match level {
+            0 => NotificationLevel::All,
+            1 => NotificationLevel::Mentions,
+            2 => NotificationLevel::Nothing,
+            _ => NotificationLevel::Inherit,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ChannelOverride {
+    pub channel_id: String,
+    pub muted: bool,
+    pub notification_level: NotificationLevel,
+}
+
+//Per guild settings from READY, DMs have them under the "@me" guild
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct GuildSettings {
+    pub guild_id: String,
+    pub muted: bool,
+    pub suppress_everyone: bool,
+    pub notification_level: NotificationLevel,
+    pub channel_overrides: Vec<ChannelOverride>,
+}
+
+impl GuildSettings {
+    pub fn from(event: &Value) -> GuildSettings {
+        let guild_id = event["guild_id"].as_str().unwrap_or("@me").to_string();
+        let muted = GuildSettings::is_muted(event);
+        let suppress_everyone = event["suppress_everyone"].as_bool().unwrap_or(false);
+        let notification_level =
+            NotificationLevel::from(event["message_notifications"].as_i64().unwrap_or(3));
+
+        let mut channel_overrides = Vec::new();
+        if let Some(v) = event["channel_overrides"].as_array() {
+            for channel in v {
+                channel_overrides.push(ChannelOverride {
+                    channel_id: channel["channel_id"].as_str().unwrap().to_string(),
+                    muted: GuildSettings::is_muted(channel),
+                    notification_level: NotificationLevel::from(
+                        channel["message_notifications"].as_i64().unwrap_or(3),
+                    ),
+                });
+            }
+        }
+
+        GuildSettings {
+            guild_id,
+            muted,
+            suppress_everyone,
+            notification_level,
+            channel_overrides,
+        }
+    }
+
+    //Same deal as read states, either a list or wrapped in "entries"
+    pub fn from_list(event: &Value) -> Vec<GuildSettings> {
+        let settings = &event["user_guild_settings"];
+        let entries = match settings.as_array() {
+            Some(v) => v,
+            None => match settings["entries"].as_array() {
+                Some(v) => v,
+                None => return Vec::new(),
+            },
+        };
+
+        entries.iter().map(GuildSettings::from).collect()
+    }
+
+    //Timed mutes stay in the settings after they run out
+    fn is_muted(event: &Value) -> bool {
+        if !event["muted"].as_bool().unwrap_or(false) {
+            return false;
+        }
+
+        let end_time = event["mute_config"]["end_time"].as_str();
+        match end_time.map(chrono::DateTime::parse_from_rfc3339) {
+            Some(Ok(end)) => end > chrono::Utc::now(),
+            _ => true,
+        }
+    }
+
+    pub fn channel_override(&self, channel_id: &str) -> Option<&ChannelOverride> {
+        self.channel_overrides
+            .iter()
+            .find(|channel| channel.channel_id == channel_id)
+    }
+}
+
+//Snowflakes are strings in JSON but they are ordered like numbers
+pub fn snowflake_newer(a: &str, b: &str) -> bool {
+    let a = a.parse::<u64>().unwrap_or(0);
+    let b = b.parse::<u64>().unwrap_or(0);
+    a > b
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Msg {
+    pub id: String,
+    pub channel_id: String,
+    //Only set for messages from the gateway
+    pub guild_id: Option<String>,
+    pub user: User,
+    //Only on gateway messages from guilds
+    pub member: Option<Member>,
+    pub content: String,
+    //ids of the users pinged
+    pub mentions: Vec<String>,
+    pub mention_everyone: bool,
+    pub mention_roles: Vec<String>,
+    pub reactions: Vec<Reaction>,
+    pub attachments: Vec<Attachment>,
+    //EPHEMERAL, LOADING and CROSSPOSTED are the ones we care about
+    pub flags: u64,
+    //Set on bot replies to an application command
+    pub interaction: Option<InteractionInfo>,
+    //Buttons and menus, one Vec per row
+    pub components: Vec<Vec<Component>>,
+    //The bot that sent it, component interactions go to it
+    pub application_id: Option<String>,
+    //Thread started from this message
+    pub thread: Option<Channel>,
+}
+
+//Published from an announcement channel to its followers
+pub const CROSSPOSTED: u64 = 1 << 0;
+pub const EPHEMERAL: u64 = 1 << 6;
+//A deferred reply, the bot edits it in later
+pub const LOADING: u64 = 1 << 7;
+
+impl Msg {
+    pub fn new() -> Msg {
+        Msg {
+            id: "222".to_string(),
+            channel_id: "333".to_string(),
+            guild_id: None,
+            user: User::new(),
+            member: None,
+            content: "Unable to open a channel without proper permission".to_string(),
+            mentions: Vec::new(),
+            mention_everyone: false,
+            mention_roles: Vec::new(),
+            reactions: Vec::new(),
+            attachments: Vec::new(),
+            flags: 0,
+            interaction: None,
+            components: Vec::new(),
+            application_id: None,
+            thread: None,
+        }
+    }
+    //Might not work for every event in mind ??
+    pub fn from(event: &Value) -> Msg {
+        let id = event["id"].as_str().unwrap().to_string();
+        let channel_id = event["channel_id"].as_str().unwrap().to_string();
+
+        let author = &event["author"];
+        let user = User::from(author);
+        let member = match event["member"].is_object() {
+            true => Some(Member::with_user(&event["member"], user.clone())),
+            false => None,
+        };
+
+        let content = event["content"].as_str().unwrap().to_string();
+
+        let guild_id = event["guild_id"].as_str().map(|v| v.to_string());
+        let mentions = match event["mentions"].as_array() {
+            Some(v) => v
+                .iter()
+                .filter_map(|user| user["id"].as_str())
+                .map(|id| id.to_string())
+                .collect(),
+            None => Vec::new(),
+        };
+        let mention_everyone = event["mention_everyone"].as_bool().unwrap_or(false);
+        let mention_roles = string_list(&event["mention_roles"]);
+        let reactions = Reaction::from_list(&event["reactions"]);
+        let attachments = Attachment::from_list(&event["attachments"]);
+        let flags = event["flags"].as_u64().unwrap_or(0);
+        let interaction = InteractionInfo::from(event);
+        let components = Component::from_rows(&event["components"]);
+        let thread = match event["thread"].is_object() {
+            true => Some(Channel::from(&event["thread"])),
+            false => None,
+        };
+        //Bots' user id is their application id, webhooks say it outright
+        let application_id = match event["application_id"].as_str() {
+            Some(v) => Some(v.to_string()),
+            None if user.bot => Some(user.id.clone()),
+            None => None,
+        };
+
+        Msg {
+            id,
+            channel_id,
+            guild_id,
+            user,
+            member,
+            content,
+            mentions,
+            mention_everyone,
+            mention_roles,
+            reactions,
+            attachments,
+            flags,
+            interaction,
+            components,
+            application_id,
+            thread,
+        }
+    }
+
+    pub fn is_ephemeral(&self) -> bool {
+        self.flags & EPHEMERAL != 0
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.flags & LOADING != 0
+    }
+
+    pub fn is_published(&self) -> bool {
+        self.flags & CROSSPOSTED != 0
+    }
+
+    //Someone reacted or took a reaction back, we is our own user id
+    pub fn apply_reaction(&mut self, update: &ReactionUpdate, we: &str) {
+        let ours = update.user_id == we;
+        let index = self
+            .reactions
+            .iter()
+            .position(|reaction| reaction.emoji == update.emoji);
+
+        match (index, update.added) {
+            (Some(i), true) => {
+                self.reactions[i].count += 1;
+                self.reactions[i].me |= ours;
+            }
+            (None, true) => self.reactions.push(Reaction {
+                emoji: update.emoji.clone(),
+                count: 1,
+                me: ours,
+            }),
+            (Some(i), false) => {
+                self.reactions[i].count -= 1;
+                if ours {
+                    self.reactions[i].me = false;
+                }
+                if self.reactions[i].count <= 0 {
+                    self.reactions.remove(i);
+                }
+            }
+            (None, false) => (),
+        }
+    }
+}
+
+//A file on a message, url points at discord's CDN
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Attachment {
+    pub id: String,
+    pub filename: String,
+    pub url: String,
+    pub size: u64,
+}
+
+impl Attachment {
+    pub fn from(event: &Value) -> Attachment {
+        let id = event["id"].as_str().unwrap().to_string();
+        let filename = event["filename"].as_str().unwrap_or("file").to_string();
+        let url = event["url"].as_str().unwrap_or_default().to_string();
+        let size = event["size"].as_u64().unwrap_or(0);
+
+        Attachment {
+            id,
+            filename,
+            url,
+            size,
+        }
+    }
+
+    pub fn from_list(attachments: &Value) -> Vec<Attachment> {
+        match attachments.as_array() {
+            Some(v) => v.iter().map(Attachment::from).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+//Unicode emoji only have a name, custom ones have an id too
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ReactionEmoji {
+    pub id: Option<String>,
+    pub name: String,
+}
+
+impl ReactionEmoji {
+    pub fn from(event: &Value) -> ReactionEmoji {
+        let id = event["id"].as_str().map(|v| v.to_string());
+        let name = event["name"].as_str().unwrap_or_default().to_string();
+
+        ReactionEmoji { id, name }
+    }
+
+    //How the reaction endpoints want it, name:id for custom emoji
+    pub fn api_name(&self) -> String {
+        match &self.id {
+            Some(id) => format!("{}:{}", self.name, id),
+            None => self.name.clone(),
+        }
+    }
+
+    //Custom emoji can't be drawn in a terminal
+    pub fn display(&self) -> String {
+        match &self.id {
+            Some(_) => format!(":{}:", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Reaction {
+    pub emoji: ReactionEmoji,
+    pub count: i64,
+    //Whether we are one of the people who reacted
+    pub me: bool,
+}
+
+impl Reaction {
+    pub fn from(event: &Value) -> Reaction {
+        let emoji = ReactionEmoji::from(&event["emoji"]);
+        let count = event["count"].as_i64().unwrap_or(0);
+        let me = event["me"].as_bool().unwrap_or(false);
+
+        Reaction { emoji, count, me }
+    }
+
+    pub fn from_list(reactions: &Value) -> Vec<Reaction> {
+        match reactions.as_array() {
+            Some(v) => v.iter().map(Reaction::from).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+//MESSAGE_REACTION_ADD/REMOVE, one user's reaction on one message
+#[derive(Clone, Debug)]
+pub struct ReactionUpdate {
+    pub channel_id: String,
+    pub message_id: String,
+    pub user_id: String,
+    pub emoji: ReactionEmoji,
+    //false when it was removed
+    pub added: bool,
+}
+
+impl ReactionUpdate {
+    pub fn from(event: &Value, added: bool) -> ReactionUpdate {
+        let channel_id = event["channel_id"].as_str().unwrap().to_string();
+        let message_id = event["message_id"].as_str().unwrap().to_string();
+        let user_id = event["user_id"].as_str().unwrap().to_string();
+        let emoji = ReactionEmoji::from(&event["emoji"]);
+
+        ReactionUpdate {
+            channel_id,
+            message_id,
+            user_id,
+            emoji,
+            added,
+        }
+    }
+}
+
+//Who ran which command, for the line above a bot's reply
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct InteractionInfo {
+    pub user: User,
+    pub command: String,
+}
+
+impl InteractionInfo {
+    //Older messages have "interaction", newer ones "interaction_metadata"
+    pub fn from(message: &Value) -> Option<InteractionInfo> {
+        let interaction = [&message["interaction"], &message["interaction_metadata"]]
+            .into_iter()
+            .find(|v| v["user"].is_object())?;
+        let user = User::from(&interaction["user"]);
+        let command = interaction["name"]
+            .as_str()
+            .or(message["interaction"]["name"].as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        Some(InteractionInfo { user, command })
+    }
+}
+
+//A bot's slash command, from the guild or DM command index
+#[derive(Clone, Debug)]
+pub struct ApplicationCommand {
+    pub id: String,
+    pub application_id: String,
+    pub version: String,
+    pub name: String,
+    pub description: String,
+    //Name of the bot it belongs to
+    pub application: String,
+    pub options: Vec<CommandOption>,
+    //Discord wants the whole thing sent back with every use
+    pub raw: Value,
+}
+
+impl ApplicationCommand {
+    //Only chat input commands, user and message commands live in context menus
+    pub fn from_index(index: &Value) -> Vec<ApplicationCommand> {
+        let applications = index["applications"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let application_name = |id: &str| {
+            applications
+                .iter()
+                .find(|application| application["id"].as_str() == Some(id))
+                .and_then(|application| application["name"].as_str())
+                .unwrap_or("bot")
+                .to_string()
+        };
+
+        let commands = match index["application_commands"].as_array() {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+        commands
+            .iter()
+            .filter(|command| command["type"].as_u64().unwrap_or(1) == 1)
+            .map(|command| {
+                let application_id = command["application_id"].as_str().unwrap_or_default();
+                ApplicationCommand {
+                    id: command["id"].as_str().unwrap_or_default().to_string(),
+                    application_id: application_id.to_string(),
+                    version: command["version"].as_str().unwrap_or_default().to_string(),
+                    name: command["name"].as_str().unwrap_or_default().to_string(),
+                    description: command["description"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    application: application_name(application_id),
+                    options: CommandOption::from_list(&command["options"]),
+                    raw: command.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum OptionKind {
+    SubCommand,
+    SubCommandGroup,
+    String,
+    Integer,
+    Boolean,
+    User,
+    Channel,
+    Role,
+    Mentionable,
+    Number,
+    Attachment,
+}
+
+impl OptionKind {
+    pub fn from(kind: u64) -> OptionKind {
+        match kind {
+            1 => OptionKind::SubCommand,
+            2 => OptionKind::SubCommandGroup,
+            4 => OptionKind::Integer,
+            5 => OptionKind::Boolean,
+            6 => OptionKind::User,
+            7 => OptionKind::Channel,
+            8 => OptionKind::Role,
+            9 => OptionKind::Mentionable,
+            10 => OptionKind::Number,
+            11 => OptionKind::Attachment,
+            _ => OptionKind::String,
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        match self {
+            OptionKind::SubCommand => 1,
+            OptionKind::SubCommandGroup => 2,
+            OptionKind::String => 3,
+            OptionKind::Integer => 4,
+            OptionKind::Boolean => 5,
+            OptionKind::User => 6,
+            OptionKind::Channel => 7,
+            OptionKind::Role => 8,
+            OptionKind::Mentionable => 9,
+            OptionKind::Number => 10,
+            OptionKind::Attachment => 11,
+        }
+    }
+
+    //What shows up in completion and usage errors
+    pub fn name(&self) -> &str {
+        match self {
+            OptionKind::SubCommand | OptionKind::SubCommandGroup => "subcommand",
+            OptionKind::String => "text",
+            OptionKind::Integer => "integer",
+            OptionKind::Boolean => "true/false",
+            OptionKind::User => "@user",
+            OptionKind::Channel => "#channel",
+            OptionKind::Role => "@role",
+            OptionKind::Mentionable => "@user or @role",
+            OptionKind::Number => "number",
+            OptionKind::Attachment => "file",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CommandOption {
+    pub kind: OptionKind,
+    pub name: String,
+    pub description: String,
+    pub required: bool,
+    //(name shown, value sent)
+    pub choices: Vec<(String, Value)>,
+    //Subcommands have their own options
+    pub options: Vec<CommandOption>,
+}
+
+impl CommandOption {
+    pub fn from_list(options: &Value) -> Vec<CommandOption> {
+        let options = match options.as_array() {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        options
+            .iter()
+            .map(|option| {
+                let choices = match option["choices"].as_array() {
+                    Some(v) => v
+                        .iter()
+                        .map(|choice| {
+                            let name = choice["name"].as_str().unwrap_or_default().to_string();
+                            (name, choice["value"].clone())
+                        })
+                        .collect(),
+                    None => Vec::new(),
+                };
+
+                CommandOption {
+                    kind: OptionKind::from(option["type"].as_u64().unwrap_or(3)),
+                    name: option["name"].as_str().unwrap_or_default().to_string(),
+                    description: option["description"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                    required: option["required"].as_bool().unwrap_or(false),
+                    choices,
+                    options: CommandOption::from_list(&option["options"]),
+                }
+            })
+            .collect()
+    }
+
+    pub fn is_subcommand(&self) -> bool {
+        matches!(
+            self.kind,
+            OptionKind::SubCommand | OptionKind::SubCommandGroup
+        )
+    }
+}
+
+//Something under a bot's message that can be clicked
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Component {
+    Button(Button),
+    Select(SelectMenu),
+}
+
+impl Component {
+    //Action rows of buttons or menus, the newer layout components are skipped
+    pub fn from_rows(rows: &Value) -> Vec<Vec<Component>> {
+        let rows = match rows.as_array() {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        rows.iter()
+            .filter(|row| row["type"].as_u64() == Some(1))
+            .map(|row| {
+                let components = row["components"].as_array().cloned().unwrap_or_default();
+                components.iter().filter_map(Component::from).collect()
+            })
+            .filter(|row: &Vec<Component>| !row.is_empty())
+            .collect()
+    }
+
+    pub fn from(component: &Value) -> Option<Component> {
+        let custom_id = component["custom_id"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let disabled = component["disabled"].as_bool().unwrap_or(false);
+
+        match component["type"].as_u64()? {
+            2 => {
+                let emoji = match component["emoji"].is_object() {
+                    true => Some(ReactionEmoji::from(&component["emoji"])),
+                    false => None,
+                };
+                Some(Component::Button(Button {
+                    custom_id,
+                    label: component["label"].as_str().unwrap_or_default().to_string(),
+                    emoji,
+                    style: component["style"].as_u64().unwrap_or(2),
+                    url: component["url"].as_str().map(|v| v.to_string()),
+                    disabled,
+                }))
+            }
+            kind @ (3 | 5..=8) => {
+                let options = match component["options"].as_array() {
+                    Some(v) => v.iter().map(SelectOption::from).collect(),
+                    None => Vec::new(),
+                };
+                Some(Component::Select(SelectMenu {
+                    custom_id,
+                    kind,
+                    placeholder: component["placeholder"]
+                        .as_str()
+                        .unwrap_or("Make a selection")
+                        .to_string(),
+                    options,
+                    min_values: component["min_values"].as_u64().unwrap_or(1),
+                    max_values: component["max_values"].as_u64().unwrap_or(1),
+                    disabled,
+                }))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn disabled(&self) -> bool {
+        match self {
+            Component::Button(button) => button.disabled,
+            Component::Select(menu) => menu.disabled,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Button {
+    pub custom_id: String,
+    pub label: String,
+    pub emoji: Option<ReactionEmoji>,
+    //1 primary, 2 secondary, 3 success, 4 danger, 5 link
+    pub style: u64,
+    //Only link buttons, they open this instead of telling the bot
+    pub url: Option<String>,
+    pub disabled: bool,
+}
+
+impl Button {
+    pub fn text(&self) -> String {
+        match &self.emoji {
+            Some(emoji) if self.label.is_empty() => emoji.display(),
+            Some(emoji) => format!("{} {}", emoji.display(), self.label),
+            None => self.label.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SelectMenu {
+    pub custom_id: String,
+    //3 is our own options, 5 to 8 pick users, roles, mentionables or channels
+    pub kind: u64,
+    pub placeholder: String,
+    pub options: Vec<SelectOption>,
+    pub min_values: u64,
+    pub max_values: u64,
+    pub disabled: bool,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SelectOption {
+    pub label: String,
+    pub value: String,
+    pub description: Option<String>,
+    pub default: bool,
+}
+
+impl SelectOption {
+    pub fn from(option: &Value) -> SelectOption {
+        SelectOption {
+            label: option["label"].as_str().unwrap_or_default().to_string(),
+            value: option["value"].as_str().unwrap_or_default().to_string(),
+            description: option["description"].as_str().map(|v| v.to_string()),
+            default: option["default"].as_bool().unwrap_or(false),
+        }
+    }
+}
+
+//Every active thread of some channels, sent when we gain access to them
+#[derive(Clone, Debug)]
+pub struct ThreadListSync {
+    pub guild_id: String,
+    //None means the whole guild
+    pub channel_ids: Option<Vec<String>>,
+    pub threads: Vec<Channel>,
+}
+
+impl ThreadListSync {
+    pub fn from(event: &Value) -> ThreadListSync {
+        let guild_id = event["guild_id"].as_str().unwrap_or_default().to_string();
+        let channel_ids = event["channel_ids"].as_array().map(|ids| {
+            ids.iter()
+                .filter_map(|id| id.as_str())
+                .map(|id| id.to_string())
+                .collect()
+        });
+        //Members are only sent for threads we're in
+        let joined: Vec<&str> = match event["members"].as_array() {
+            Some(v) => v
+                .iter()
+                .filter_map(|member| member["id"].as_str())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let threads = match event["threads"].as_array() {
+            Some(v) => v
+                .iter()
+                .map(|thread| {
+                    let mut thread = Channel::from(thread);
+                    thread.guild_id = guild_id.clone();
+                    if let Some(info) = thread.thread.as_mut() {
+                        info.joined |= joined.contains(&thread.id.as_str());
+                    }
+                    thread
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        ThreadListSync {
+            guild_id,
+            channel_ids,
+            threads,
+        }
+    }
+}
+
+//Users joining or leaving a thread, we only care when it's us
+#[derive(Clone, Debug)]
+pub struct ThreadMembersUpdate {
+    pub thread_id: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl ThreadMembersUpdate {
+    //THREAD_MEMBER_UPDATE, always about us joining
+    pub fn from_member(event: &Value) -> ThreadMembersUpdate {
+        ThreadMembersUpdate {
+            thread_id: event["id"].as_str().unwrap_or_default().to_string(),
+            added: event["user_id"]
+                .as_str()
+                .map(|v| vec![v.to_string()])
+                .unwrap_or_default(),
+            removed: Vec::new(),
+        }
+    }
+
+    pub fn from(event: &Value) -> ThreadMembersUpdate {
+        let ids = |list: &Value, key: Option<&str>| -> Vec<String> {
+            match list.as_array() {
+                Some(v) => v
+                    .iter()
+                    .filter_map(|item| match key {
+                        Some(key) => item[key].as_str(),
+                        None => item.as_str(),
+                    })
+                    .map(|id| id.to_string())
+                    .collect(),
+                None => Vec::new(),
+            }
+        };
+
+        ThreadMembersUpdate {
+            thread_id: event["id"].as_str().unwrap_or_default().to_string(),
+            added: ids(&event["added_members"], Some("user_id")),
+            removed: ids(&event["removed_member_ids"], None),
+        }
+    }
+}
+
+//Someone connected to a voice or stage channel
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct VoiceState {
+    pub user_id: String,
+    pub guild_id: String,
+    //None once they disconnect
+    pub channel_id: Option<String>,
+    //Muted or deafened by themselves or by a moderator
+    pub mute: bool,
+    pub deaf: bool,
+    //Go Live and camera
+    pub streaming: bool,
+    pub video: bool,
+    //Stage audience, they can't talk until they're made a speaker
+    pub suppress: bool,
+    //Only VOICE_STATE_UPDATE comes with one
+    pub member: Option<Member>,
+}
+
+impl VoiceState {
+    pub fn from(event: &Value) -> VoiceState {
+        let flag = |key: &str| event[key].as_bool().unwrap_or(false);
+        let member = match event["member"]["user"].is_object() {
+            true => Some(Member::from(&event["member"])),
+            false => None,
+        };
+
+        VoiceState {
+            user_id: event["user_id"].as_str().unwrap_or_default().to_string(),
+            guild_id: event["guild_id"].as_str().unwrap_or_default().to_string(),
+            channel_id: event["channel_id"].as_str().map(|v| v.to_string()),
+            mute: flag("mute") || flag("self_mute"),
+            deaf: flag("deaf") || flag("self_deaf"),
+            streaming: flag("self_stream"),
+            video: flag("self_video"),
+            suppress: flag("suppress"),
+            member,
+        }
+    }
+
+    //READY and GUILD_CREATE leave the guild id out
+    pub fn from_list(states: &Value, guild_id: &str) -> Vec<VoiceState> {
+        match states.as_array() {
+            Some(v) => v
+                .iter()
+                .map(|state| VoiceState {
+                    guild_id: guild_id.to_string(),
+                    ..VoiceState::from(state)
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    //"🔇🎧📷" or whatever applies, streaming gets its own LIVE tag
+    pub fn indicators(&self) -> String {
+        let mut indicators = String::new();
+        if self.mute || self.suppress {
+            indicators.push('🔇');
+        }
+        if self.deaf {
+            indicators.push('🎧');
+        }
+        if self.video {
+            indicators.push('📷');
+        }
+        indicators
+    }
+}
+
+//Everyone in voice in a guild, GUILD_CREATE replaces what we had
+#[derive(Clone, Debug)]
+pub struct VoiceStateSync {
+    pub guild_id: String,
+    pub states: Vec<VoiceState>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn update(emoji: &str, user_id: &str, added: bool) -> ReactionUpdate {
+        ReactionUpdate {
+            channel_id: "1".to_string(),
+            message_id: "2".to_string(),
+            user_id: user_id.to_string(),
+            emoji: ReactionEmoji {
+                id: None,
+                name: emoji.to_string(),
+            },
+            added,
+        }
+    }
+
+    fn member(id: &str) -> MemberListItem {
+        MemberListItem::from(&json!({ "member": { "user": { "id": id, "username": id } } }))
+    }
+
+    fn member_ids(list: &[MemberListItem]) -> Vec<String> {
+        list.iter()
+            .map(|item| match item {
+                MemberListItem::Member(member) => member.user.id.clone(),
+                MemberListItem::Group { id, .. } => format!("group {}", id),
+            })
+            .collect()
+    }
+
+    fn list_update(ops: Value) -> MemberListUpdate {
+        MemberListUpdate::from(&json!({ "guild_id": "1", "id": "everyone", "ops": ops }))
+    }
+
+    #[test]
+    fn apply_reaction_adds_and_counts() {
+        let mut message = Msg::new();
+        message.apply_reaction(&update("👍", "other", true), "me");
+        message.apply_reaction(&update("👍", "me", true), "me");
+
+        assert_eq!(message.reactions.len(), 1);
+        assert_eq!(message.reactions[0].count, 2);
+        assert!(message.reactions[0].me);
+    }
+
+    #[test]
+    fn apply_reaction_removes_empty_reactions() {
+        let mut message = Msg::new();
+        message.apply_reaction(&update("👍", "other", true), "me");
+        message.apply_reaction(&update("👍", "me", true), "me");
+
+        message.apply_reaction(&update("👍", "me", false), "me");
+        assert_eq!(message.reactions[0].count, 1);
+        assert!(!message.reactions[0].me);
+
+        message.apply_reaction(&update("👍", "other", false), "me");
+        assert!(message.reactions.is_empty());
+    }
+
+    #[test]
+    fn apply_reaction_ignores_removing_unknown_reactions() {
+        let mut message = Msg::new();
+        message.apply_reaction(&update("🎉", "other", false), "me");
+        assert!(message.reactions.is_empty());
+    }
+
+    #[test]
+    fn member_list_sync_replaces_its_range() {
+        let mut list = vec![member("a"), member("b"), member("c")];
+        let update = list_update(json!([{
+            "op": "SYNC",
+            "range": [0, 1],
+            "items": [
+                { "group": { "id": "online", "count": 1 } },
+                { "member": { "user": { "id": "x", "username": "x" } } },
+            ],
+        }]));
+        update.apply(&mut list);
+        assert_eq!(member_ids(&list), ["group online", "x", "c"]);
+    }
+
+    #[test]
+    fn member_list_ops_go_by_index() {
+        let mut list = vec![member("a"), member("b")];
+        let update = list_update(json!([
+            { "op": "INSERT", "index": 1, "item": { "member": { "user": { "id": "new", "username": "new" } } } },
+            { "op": "UPDATE", "index": 0, "item": { "member": { "user": { "id": "changed", "username": "changed" } } } },
+            { "op": "DELETE", "index": 2 },
+        ]));
+        update.apply(&mut list);
+        assert_eq!(member_ids(&list), ["changed", "new"]);
+    }
+
+    #[test]
+    fn member_list_ops_out_of_range_do_nothing() {
+        let mut list = vec![member("a")];
+        let update = list_update(json!([
+            { "op": "UPDATE", "index": 5, "item": { "member": { "user": { "id": "x", "username": "x" } } } },
+            { "op": "DELETE", "index": 5 },
+            { "op": "INVALIDATE", "range": [0, 99] },
+        ]));
+        update.apply(&mut list);
+        assert_eq!(member_ids(&list), ["a"]);
+    }
+
+    #[test]
+    fn member_list_update_knows_what_it_touched() {
+        let update = list_update(json!([
+            { "op": "SYNC", "range": [0, 0], "items": [{ "member": { "user": { "id": "a", "username": "a" } } }] },
+            { "op": "DELETE", "index": 3 },
+        ]));
+        assert!(update.has_sync());
+        let ids: Vec<&str> = update
+            .members()
+            .iter()
+            .map(|m| m.user.id.as_str())
+            .collect();
+        assert_eq!(ids, ["a"]);
+
+        let update = list_update(json!([{ "op": "DELETE", "index": 0 }]));
+        assert!(!update.has_sync());
+    }
+
+    fn permission_guild(overwrites: Value) -> (Guild, Channel) {
+        let mut guild = Guild::from_partial(&json!({ "id": "g", "name": "test" }));
+        guild.roles = Role::from_list(&json!([
+            { "id": "g", "permissions": SEND_MESSAGES.to_string() },
+            { "id": "mod", "permissions": MANAGE_MESSAGES.to_string() },
+            { "id": "admin", "permissions": ADMINISTRATOR.to_string() },
+        ]));
+        let channel = Channel::from(&json!({
+            "id": "c",
+            "type": 5,
+            "guild_id": "g",
+            "permission_overwrites": overwrites,
+        }));
+        guild.channels.push(channel.clone());
+        (guild, channel)
+    }
+
+    #[test]
+    fn permissions_add_up_roles() {
+        let (mut guild, channel) = permission_guild(json!([]));
+        assert_eq!(guild.permissions(&channel, "me"), SEND_MESSAGES);
+
+        guild.own_roles = vec!["mod".to_string()];
+        assert_eq!(
+            guild.permissions(&channel, "me"),
+            SEND_MESSAGES | MANAGE_MESSAGES
+        );
+    }
+
+    #[test]
+    fn permissions_apply_overwrites_in_order() {
+        let (mut guild, channel) = permission_guild(json!([
+            { "id": "g", "type": 0, "allow": "0", "deny": SEND_MESSAGES.to_string() },
+            { "id": "mod", "type": 0, "allow": SEND_MESSAGES.to_string(), "deny": "0" },
+            { "id": "me", "type": 1, "allow": "0", "deny": MANAGE_MESSAGES.to_string() },
+        ]));
+        assert_eq!(guild.permissions(&channel, "me"), 0);
+
+        guild.own_roles = vec!["mod".to_string()];
+        assert_eq!(guild.permissions(&channel, "me"), SEND_MESSAGES);
+        assert_eq!(
+            guild.permissions(&channel, "someone"),
+            SEND_MESSAGES | MANAGE_MESSAGES
+        );
+    }
+
+    #[test]
+    fn permissions_owner_and_admin_can_do_anything() {
+        let (mut guild, channel) = permission_guild(json!([
+            { "id": "g", "type": 0, "allow": "0", "deny": SEND_MESSAGES.to_string() },
+        ]));
+        guild.owner_id = "me".to_string();
+        assert_eq!(guild.permissions(&channel, "me"), u64::MAX);
+
+        guild.owner_id = String::new();
+        guild.own_roles = vec!["admin".to_string()];
+        assert_eq!(guild.permissions(&channel, "me"), u64::MAX);
+    }
+
+    #[test]
+    fn guild_reads_default_notifications() {
+        let guild = |id: &str, extra: Value| {
+            let mut guild =
+                json!({ "id": id, "name": id, "channels": [], "roles": [], "emojis": [] });
+            guild
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            guild
+        };
+        let ready = json!({
+            "guilds": [
+                guild("1", json!({ "default_message_notifications": 0 })),
+                guild("2", json!({ "properties": { "default_message_notifications": 1 } })),
+                guild("3", json!({})),
+            ],
+        });
+        let levels: Vec<NotificationLevel> = Guild::from_list(&ready)
+            .iter()
+            .map(|guild| guild.default_notifications)
+            .collect();
+        assert_eq!(
+            levels,
+            [
+                NotificationLevel::All,
+                NotificationLevel::Mentions,
+                NotificationLevel::Mentions
+            ]
+        );
+    }
+
+    #[test]
+    fn relationship_kind_ignores_unknown_types() {
+        assert_eq!(RelationshipKind::from(1), RelationshipKind::Friend);
+        assert_eq!(RelationshipKind::from(5), RelationshipKind::Other);
+    }
 }
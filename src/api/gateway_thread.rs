@@ -13,21 +13,24 @@ btw people's email address is public through the api I think, weird
 */
 
 use url::Url;
-use tokio_tungstenite::tungstenite::{connect, Message, WebSocket};
+use tokio_tungstenite::tungstenite::{self, connect, Message, WebSocket};
 use tokio_tungstenite::tungstenite::stream::MaybeTlsStream;
+use std::io::ErrorKind;
 use std::net::TcpStream;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use serde_json::{self, Value};
 use std::thread;
 use std::sync::mpsc;
 
 use crate::api::data::*;
 
-pub fn start_thread(token: &String) -> mpsc::Receiver<GatewayResponse> {
+//Gives back events from discord and a sender for payloads to send to it (op 14 etc)
+pub fn start_thread(token: &str) -> (mpsc::Receiver<GatewayResponse>, mpsc::Sender<Value>) {
     let (tx, rx) = mpsc::channel();
+    let (command_tx, command_rx) = mpsc::channel::<Value>();
 
     let gateway_url = "wss://gateway.discord.gg/?v=9&encoding=json";
-    let (mut socket, response) = connect(
+    let (mut socket, _response) = connect(
         Url::parse(gateway_url).unwrap()
     ).expect("Can't connect");
 
@@ -43,23 +46,42 @@ pub fn start_thread(token: &String) -> mpsc::Receiver<GatewayResponse> {
     let ready = read_json_event(&mut socket).expect("Couldn't get ready event");
     ready_event(&tx, ready);
 
+    //Reads time out so the loop still gets to send stuff when discord is quiet
+    set_read_timeout(&mut socket, Duration::from_millis(250));
+
     thread::spawn(move || {
         let mut timer = Instant::now();
+        //Ends when the socket dies, the ui gets told and the command sender stops working
         loop {
-            let event = read_json_event(&mut socket);
-            // dbg!(&event);
-            match &event {
-                Ok(v) => (),
-                Err(v) => {println!("Gateway disconnected");
-                           continue;},
+            //Heartbeat here
+            //A thread would have to borrow the socket and it was a pain
+            let elapsed = timer.elapsed().as_millis() as i64;
+            if hb_interval <= elapsed {
+                if heartbeat(&mut socket).is_err() {
+                    break;
+                }
+                timer = Instant::now();
+            }
+
+            //Whatever the ui wants to tell discord
+            let sent = command_rx
+                .try_iter()
+                .all(|command| socket.write_message(Message::Text(command.to_string())).is_ok());
+            if !sent {
+                break;
             }
-            
-            let event = event.unwrap();
+
+            let event = match poll_json_event(&mut socket) {
+                Ok(Some(v)) => v,
+                Ok(None) => continue,
+                Err(_) => break,
+            };
+            // dbg!(&event);
 
             let op_code = event["op"].as_i64().unwrap();
             // dbg!(op_code);
-            if op_code == 1 {
-                heartbeat(&mut socket);
+            if op_code == 1 && heartbeat(&mut socket).is_err() {
+                break;
             }
 
             //Should put all the events in a list or smthn
@@ -67,39 +89,46 @@ pub fn start_thread(token: &String) -> mpsc::Receiver<GatewayResponse> {
                 let event_name = event["t"].as_str().unwrap();
                 match event_name {
                     "MESSAGE_CREATE" => {message_created(&tx, &event);},
-                    "MESSAGE_REACTION_ADD" => (),
-                    "MESSAGE_REACTION_REMOVE" => (),
+                    "MESSAGE_UPDATE" => {message_updated(&tx, &event);},
+                    "INTERACTION_FAILURE" => {tx.send(GatewayResponse::interaction_failure()).unwrap();},
+                    "PRESENCE_UPDATE" => {presence_updated(&tx, &event);},
+                    "MESSAGE_ACK" => {message_acked(&tx, &event);},
+                    "USER_GUILD_SETTINGS_UPDATE" => {guild_settings_updated(&tx, &event);},
+                    "GUILD_MEMBER_LIST_UPDATE" => {member_list_updated(&tx, &event);},
+                    "GUILD_MEMBERS_CHUNK" => {members_chunk(&tx, &event);},
+                    "MESSAGE_REACTION_ADD" => {reaction_updated(&tx, &event, true);},
+                    "MESSAGE_REACTION_REMOVE" => {reaction_updated(&tx, &event, false);},
+                    "THREAD_CREATE" | "THREAD_UPDATE" | "THREAD_DELETE" => {thread_changed(&tx, event_name, &event);},
+                    "THREAD_LIST_SYNC" => {thread_list_synced(&tx, &event);},
+                    "THREAD_MEMBER_UPDATE" => {thread_members_updated(&tx, ThreadMembersUpdate::from_member(&event["d"]));},
+                    "THREAD_MEMBERS_UPDATE" => {thread_members_updated(&tx, ThreadMembersUpdate::from(&event["d"]));},
                     "TYPING_START" => (),
                     "CHANNEL_CREATE" => (),
-                    "GUILD_CREATE" => (),
+                    "VOICE_STATE_UPDATE" => {voice_state_updated(&tx, &event);},
+                    "GUILD_CREATE" => {guild_created(&tx, &event);},
                     "GUILD_DELETE" => (),
                     _ => ()
                 }
             }
-            
-            //Heartbeat here
-            //A thread would have to borrow the socket and it was a pain
-            let elapsed = timer.elapsed().as_millis() as i64;
-            if hb_interval <= elapsed {
-                heartbeat(&mut socket);
-                timer = Instant::now();
-            }
         }
+
+        //The ui may have quit already
+        let _ = tx.send(GatewayResponse::disconnected());
     });
 
-    rx
+    (rx, command_tx)
 }
 
 //Each event has an attached sequence number
 //Heartbeats need to include latest sequence number
 //^^^ Didn't use it in python test and had no problems. Abandoned for now
-fn heartbeat(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) {
+fn heartbeat(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> Result<(), String> {
     let reply = Message::Text(r#"{
         "op": 1,
         "d": "null"
     }"#.into());
 
-    socket.write_message(reply).expect("Hbeat failed");
+    socket.write_message(reply).map_err(|err| format!("Hbeat failed: {}", err))
 }
 
 fn identify(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, token: &str) {
@@ -128,9 +157,93 @@ fn message_created(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
     tx.send(gate_response).unwrap();
 }
 
+//Embed-only updates come without the author, nothing to redraw from those
+fn message_updated(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let message = &event["d"];
+    if !message["author"].is_object() || !message["content"].is_string() {
+        return;
+    }
+    let gate_response = GatewayResponse::msg_update(Msg::from(message));
+    tx.send(gate_response).unwrap();
+}
+
+fn presence_updated(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let presence = Presence::from(&event["d"]);
+    let gate_response = GatewayResponse::presence_update(presence);
+    tx.send(gate_response).unwrap();
+}
+
+fn message_acked(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let read_state = ReadState::from(&event["d"]);
+    let gate_response = GatewayResponse::message_ack(read_state);
+    tx.send(gate_response).unwrap();
+}
+
+fn guild_settings_updated(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let settings = GuildSettings::from(&event["d"]);
+    let gate_response = GatewayResponse::guild_settings_update(settings);
+    tx.send(gate_response).unwrap();
+}
+
+fn member_list_updated(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let update = MemberListUpdate::from(&event["d"]);
+    let gate_response = GatewayResponse::member_list_update(update);
+    tx.send(gate_response).unwrap();
+}
+
+fn members_chunk(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let chunk = MembersChunk::from(&event["d"]);
+    let gate_response = GatewayResponse::members_chunk(chunk);
+    tx.send(gate_response).unwrap();
+}
+
+fn reaction_updated(tx: &mpsc::Sender<GatewayResponse>, event: &Value, added: bool) {
+    let update = ReactionUpdate::from(&event["d"], added);
+    let gate_response = GatewayResponse::reaction_update(update);
+    tx.send(gate_response).unwrap();
+}
+
+fn thread_changed(tx: &mpsc::Sender<GatewayResponse>, event_name: &str, event: &Value) {
+    let thread = Channel::from(&event["d"]);
+    let gate_response = GatewayResponse::thread(event_name, thread);
+    tx.send(gate_response).unwrap();
+}
+
+fn thread_list_synced(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let sync = ThreadListSync::from(&event["d"]);
+    let gate_response = GatewayResponse::thread_list_sync(sync);
+    tx.send(gate_response).unwrap();
+}
+
+fn voice_state_updated(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let state = VoiceState::from(&event["d"]);
+    let gate_response = GatewayResponse::voice_state_update(state);
+    tx.send(gate_response).unwrap();
+}
+
+//Guilds we join later aren't added, but ones coming back get their voice states
+fn guild_created(tx: &mpsc::Sender<GatewayResponse>, event: &Value) {
+    let guild_id = event["d"]["id"].as_str().unwrap_or_default().to_string();
+    let states = VoiceState::from_list(&event["d"]["voice_states"], &guild_id);
+    let gate_response = GatewayResponse::guild_voice_states(VoiceStateSync { guild_id, states });
+    tx.send(gate_response).unwrap();
+}
+
+fn thread_members_updated(tx: &mpsc::Sender<GatewayResponse>, update: ThreadMembersUpdate) {
+    let gate_response = GatewayResponse::thread_members(update);
+    tx.send(gate_response).unwrap();
+}
+
 fn ready_event(tx: &mpsc::Sender<GatewayResponse>, event: Value) {
     let guilds = Guild::from_list(&event["d"]);
-    let gate_response = GatewayResponse::ready(guilds);
+    let dms = Channel::from_private_list(&event["d"]);
+    let presences = Presence::from_list(&event["d"]);
+    let read_states = ReadState::from_list(&event["d"]);
+    let guild_settings = GuildSettings::from_list(&event["d"]);
+    let user = User::from(&event["d"]["user"]);
+    let session_id = event["d"]["session_id"].as_str().unwrap_or_default().to_string();
+    let gate_response =
+        GatewayResponse::ready(guilds, dms, presences, read_states, guild_settings, user, session_id);
     tx.send(gate_response).unwrap();
 }
 
@@ -144,4 +257,69 @@ fn read_json_event(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>)
     
 
     serde_json::from_str(text_msg)
-}
\ No newline at end of file
+}
+//None if nothing came in before the read timeout, or it wasn't json (pings, close frames)
+//Err once the connection is gone
+fn poll_json_event(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>)
+        -> Result<Option<serde_json::Value>, String> {
+    match socket.read_message() {
+        Ok(msg) => {
+            let event = msg.to_text().ok().and_then(|text| serde_json::from_str(text).ok());
+            Ok(event)
+        }
+        Err(tungstenite::Error::Io(err))
+            if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(None),
+        Err(err) => Err(format!("Error reading msg: {}", err)),
+    }
+}
+
+fn set_read_timeout(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, timeout: Duration) {
+    let result = match socket.get_mut() {
+        MaybeTlsStream::Plain(stream) => stream.set_read_timeout(Some(timeout)),
+        MaybeTlsStream::NativeTls(stream) => stream.get_mut().set_read_timeout(Some(timeout)),
+        _ => Ok(()),
+    };
+
+    result.expect("Couldn't set gateway read timeout");
+}
+
+//op 14, asks discord to keep us updated on the member list of a channel
+pub fn lazy_guild_request(guild_id: &str, channel_id: &str) -> Value {
+    serde_json::json!({
+        "op": 14,
+        "d": {
+            "guild_id": guild_id,
+            "typing": true,
+            "activities": true,
+            "threads": true,
+            "channels": {
+                channel_id: [[0, 99]]
+            }
+        }
+    })
+}
+
+//op 8, asks for specific members, they come back as GUILD_MEMBERS_CHUNK
+pub fn request_members(guild_id: &str, user_ids: &[String]) -> Value {
+    serde_json::json!({
+        "op": 8,
+        "d": {
+            "guild_id": guild_id,
+            "user_ids": user_ids,
+            "presences": false
+        }
+    })
+}
+
+//op 3, changes our status for this session right away
+pub fn presence_update(status: &str) -> Value {
+    serde_json::json!({
+        "op": 3,
+        "d": {
+            "status": status,
+            "since": 0,
+            "activities": [],
+            "afk": false
+        }
+    })
+}
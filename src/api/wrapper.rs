@@ -2,12 +2,19 @@
 //Make everything return results
 
 use crate::{api::data::*, ui::channels::App};
+use reqwest::blocking::multipart::{Form, Part};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::string::String;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 
 fn get_length(list: &serde_json::Value) -> usize {
     let the_length = list.as_array();
     match the_length {
-        Some(v) => (),
+        Some(_v) => (),
         None => {
             panic!("TRIED TO GET LENGTH OF AN EMPTY RESPONSE")
         }
@@ -22,7 +29,7 @@ fn get(list: &serde_json::Value, index: usize, key: &str) -> anyhow::Result<Stri
 
     let mut value = list[index].get(key);
     match value {
-        Some(v) => (),
+        Some(_v) => (),
         None => {
             value = list.get(key);
         }
@@ -61,6 +68,8 @@ fn request_json(conn: &Connection, url: &str) -> serde_json::Value {
 }
 
 //get guilds
+//Unused since READY gives them to us, still handy for poking the API
+#[allow(dead_code)]
 pub fn guilds(conn: &Connection) -> Vec<Guild> {
     //Url changes for every request
     let url = "https://discord.com/api/v9/users/@me/guilds";
@@ -80,6 +89,7 @@ pub fn guilds(conn: &Connection) -> Vec<Guild> {
 
 //MIGHT BREAK IF THERE ARE NO TEXT OR VOICE CHANNELS IN A SERVER
 //MIGHT BE SLOW ?
+#[allow(dead_code)]
 pub fn channels(conn: &Connection, server: &Guild) -> Vec<Channel> {
     let url = format!("https://discord.com/api/v9/guilds/{}/channels", server.id);
     let response = request_json(conn, url.as_str());
@@ -94,24 +104,13 @@ pub fn channels(conn: &Connection, server: &Guild) -> Vec<Channel> {
     for i in 0..len {
         let channel = Channel::from(&response[i]);
 
-        let guild_vc = String::from("2");
         let category = String::from("4");
-        let announcement_thread = String::from("10");
-        let public_thread = String::from("11");
-        let private_thread = String::from("12");
-        let guild_stage_vc = String::from("13");
         let guild_directory = String::from("14");
-        let guild_forum = String::from("15");
 
+        //This endpoint never lists threads anyway
         let ignored_channels = Vec::from([
-            guild_vc,
             category,
-            announcement_thread,
-            public_thread,
-            private_thread,
-            guild_stage_vc,
             guild_directory,
-            guild_forum,
         ]);
 
         if ignored_channels.contains(&channel.channel_type) {
@@ -124,6 +123,7 @@ pub fn channels(conn: &Connection, server: &Guild) -> Vec<Channel> {
     channel_list
 }
 
+#[allow(dead_code)]
 pub fn find_channel(channels: &Vec<Channel>, title: &str) -> Result<Channel, &'static str> {
     for channel in channels {
         if channel.name.as_str() == title {
@@ -145,8 +145,8 @@ pub fn messages(conn: &Connection, channel: &Channel) -> anyhow::Result<Vec<Msg>
     let potential_panic = get(&response, 0, "code");
 
     match potential_panic {
-        Ok(v) => return Err("ACCESS DENIED"),
-        Err(v) => (),
+        Ok(_v) => return Err("ACCESS DENIED"),
+        Err(_v) => (),
     }
 
     let mut message_list = Vec::new();
@@ -161,7 +161,8 @@ pub fn messages(conn: &Connection, channel: &Channel) -> anyhow::Result<Vec<Msg>
     Ok(message_list)
 }
 
-pub fn friends(conn: &Connection) -> Vec<User> {
+//Friends, blocked users and pending requests all come from here
+pub fn friends(conn: &Connection) -> Vec<Relationship> {
     let url = "https://discord.com/api/v9/users/@me/relationships";
     let response = request_json(conn, url);
 
@@ -169,13 +170,211 @@ pub fn friends(conn: &Connection) -> Vec<User> {
 
     let len = get_length(&response);
     for i in 0..len {
-        let user = User::from(&response[i]);
-        friends_list.push(user);
+        let relationship = Relationship::from(&response[i]);
+        if relationship.kind == RelationshipKind::Other {
+            continue;
+        }
+        friends_list.push(relationship);
     }
 
     friends_list
 }
 
+//Accepting is the same call as sending a request back
+pub fn accept_friend(conn: &Connection, user: &User) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/users/@me/relationships/{}",
+        user.id
+    );
+
+    let response = conn.client
+        .put(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(&serde_json::json!({}))
+        .send()
+        .map_err(|err| format!("Couldn't accept friend request: {}", err))?;
+    check_response(response, "Couldn't accept friend request")
+}
+
+//Removes a friend, declines/cancels a request or unblocks, depending on the relationship
+pub fn remove_relationship(conn: &Connection, user: &User) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/users/@me/relationships/{}",
+        user.id
+    );
+
+    let response = conn.client
+        .delete(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .map_err(|err| format!("Couldn't remove relationship: {}", err))?;
+    check_response(response, "Couldn't remove relationship")
+}
+
+//Puts our reaction on a message
+pub fn add_reaction(conn: &Connection, channel_id: &str, message_id: &str, emoji: &ReactionEmoji) -> Result<(), String> {
+    let response = conn.client
+        .put(reaction_url(channel_id, message_id, emoji))
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .map_err(|err| format!("Couldn't add reaction: {}", err))?;
+    check_response(response, "Couldn't add reaction")
+}
+
+pub fn remove_reaction(conn: &Connection, channel_id: &str, message_id: &str, emoji: &ReactionEmoji) -> Result<(), String> {
+    let response = conn.client
+        .delete(reaction_url(channel_id, message_id, emoji))
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .map_err(|err| format!("Couldn't remove reaction: {}", err))?;
+    check_response(response, "Couldn't remove reaction")
+}
+
+//Unicode emoji have to be percent encoded in the path
+fn reaction_url(channel_id: &str, message_id: &str, emoji: &ReactionEmoji) -> String {
+    let emoji: String = url::form_urlencoded::byte_serialize(emoji.api_name().as_bytes()).collect();
+    format!(
+        "https://discord.com/api/v9/channels/{}/messages/{}/reactions/{}/@me",
+        channel_id, message_id, emoji
+    )
+}
+
+//Gives back the existing DM channel if there already is one
+pub fn open_dm(conn: &Connection, user: &User) -> Result<Channel, String> {
+    let url = "https://discord.com/api/v9/users/@me/channels";
+    let body = serde_json::json!({ "recipients": [user.id] });
+
+    let response = conn
+        .client
+        .post(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(&body)
+        .send()
+        .map_err(|err| format!("Couldn't open DM: {}", err))?;
+    if !response.status().is_success() {
+        return Err(error_message(response, "Couldn't open DM"));
+    }
+
+    let dm: serde_json::Value = response.json().map_err(|err| format!("Couldn't open DM: {}", err))?;
+    Ok(Channel::from(&dm))
+}
+
+//Our nickname in one guild, an empty nick resets it
+pub fn set_nick(conn: &Connection, guild_id: &str, nick: &str) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/guilds/{}/members/@me",
+        guild_id
+    );
+    let nick = match nick.is_empty() {
+        true => serde_json::Value::Null,
+        false => serde_json::Value::from(nick),
+    };
+
+    let response = conn.client
+        .patch(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(&serde_json::json!({ "nick": nick }))
+        .send()
+        .map_err(|err| format!("Couldn't change nickname: {}", err))?;
+    check_response(response, "Couldn't change nickname")
+}
+
+//Saved to the account so it sticks between sessions, the gateway gets told separately
+pub fn set_status(conn: &Connection, status: &str) -> Result<(), String> {
+    let url = "https://discord.com/api/v9/users/@me/settings";
+
+    let response = conn.client
+        .patch(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(&serde_json::json!({ "status": status }))
+        .send()
+        .map_err(|err| format!("Couldn't change status: {}", err))?;
+    check_response(response, "Couldn't change status")
+}
+
+//Searches a whole guild, or just the channel when it's a DM (guild_id "@me")
+pub fn search(conn: &Connection, guild_id: &str, channel_id: &str, query: &str) -> Result<Vec<Msg>, String> {
+    let query: String = url::form_urlencoded::byte_serialize(query.as_bytes()).collect();
+    let url = match guild_id {
+        "@me" => format!(
+            "https://discord.com/api/v9/channels/{}/messages/search?content={}",
+            channel_id, query
+        ),
+        guild_id => format!(
+            "https://discord.com/api/v9/guilds/{}/messages/search?content={}",
+            guild_id, query
+        ),
+    };
+
+    let response = conn.client
+        .get(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .map_err(|err| format!("Search failed: {}", err))?;
+    //Discord is still building the index, happens on servers nobody searched in a while
+    if response.status() == reqwest::StatusCode::ACCEPTED {
+        return Err("Discord is still indexing, try again in a bit".to_string());
+    }
+    if !response.status().is_success() {
+        return Err(format!("Search failed: {}", response.status()));
+    }
+
+    let body: serde_json::Value = response.json().map_err(|err| format!("Search failed: {}", err))?;
+    //Every hit comes as a list, the match itself is the first one
+    let results = body["messages"]
+        .as_array()
+        .map(|hits| hits.iter().filter(|hit| hit[0].is_object()).map(|hit| Msg::from(&hit[0])).collect())
+        .unwrap_or_default();
+    Ok(results)
+}
+
+pub fn leave_guild(conn: &Connection, guild_id: &str) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/users/@me/guilds/{}",
+        guild_id
+    );
+
+    let response = conn.client
+        .delete(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(&serde_json::json!({ "lurking": false }))
+        .send()
+        .map_err(|err| format!("Couldn't leave: {}", err))?;
+    check_response(response, "Couldn't leave")
+}
+
+//Leaves a group DM, or just hides a normal one
+pub fn close_channel(conn: &Connection, channel_id: &str) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}",
+        channel_id
+    );
+
+    let response = conn.client
+        .delete(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .map_err(|err| format!("Couldn't leave: {}", err))?;
+    check_response(response, "Couldn't leave")
+}
+
+//Ok on success, otherwise discord's own explanation if it gave one
+fn check_response(response: reqwest::blocking::Response, failed: &str) -> Result<(), String> {
+    match response.status().is_success() {
+        true => Ok(()),
+        false => Err(error_message(response, failed)),
+    }
+}
+
+fn error_message(response: reqwest::blocking::Response, failed: &str) -> String {
+    let status = response.status();
+    let body: serde_json::Value = response.json().unwrap_or_default();
+    match body["message"].as_str() {
+        Some(v) => format!("{}: {}", failed, v),
+        None => format!("{}: {}", failed, status),
+    }
+}
+
 pub fn send_message(app: &mut App, input: &String) {
     let channel_id = app.get_channel().id;
     let conn = &app.conn;
@@ -188,7 +387,7 @@ pub fn send_message(app: &mut App, input: &String) {
         "https://discord.com/api/v9/channels/{}/messages",
         channel_id
     );
-    let response = client
+    client
         .post(url)
         .header(header.0, header.1)
         .form(&params)
@@ -196,3 +395,293 @@ pub fn send_message(app: &mut App, input: &String) {
         .expect("Failed to send input");
 }
 
+//Sends files as one message, caption can be empty
+//sent counts the bytes read from the files so far, for a progress bar
+pub fn upload_files(conn: &Connection, channel_id: &str, caption: &str, files: &[PathBuf], sent: Arc<AtomicU64>) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}/messages",
+        channel_id
+    );
+
+    let payload = serde_json::json!({ "content": caption });
+    let mut form = Form::new().text("payload_json", payload.to_string());
+    for (i, path) in files.iter().enumerate() {
+        let file = File::open(path).map_err(|err| format!("Couldn't open {}: {}", path.display(), err))?;
+        let length = file.metadata().map(|v| v.len()).unwrap_or(0);
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+
+        let reader = ProgressReader { inner: file, sent: sent.clone() };
+        let part = Part::reader_with_length(reader, length).file_name(name);
+        form = form.part(format!("files[{}]", i), part);
+    }
+
+    let response = conn.client
+        .post(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .multipart(form)
+        //The default 30 seconds isn't enough for big files
+        .timeout(Duration::from_secs(60 * 30))
+        .send()
+        .map_err(|err| format!("Upload failed: {}", err))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    if status == reqwest::StatusCode::PAYLOAD_TOO_LARGE {
+        return Err("Discord says that's over your upload limit".to_string());
+    }
+
+    let body: serde_json::Value = response.json().unwrap_or_default();
+    match body["message"].as_str() {
+        Some(v) => Err(format!("Upload failed: {}", v)),
+        None => Err(format!("Upload failed: {}", status)),
+    }
+}
+
+//Streams a file from the CDN to path, received counts bytes as they come in
+pub fn download_file(conn: &Connection, url: &str, path: &PathBuf, received: Arc<AtomicU64>) -> Result<(), String> {
+    //No token here, the CDN doesn't need it
+    let mut response = conn.client
+        .get(url)
+        .timeout(Duration::from_secs(60 * 30))
+        .send()
+        .and_then(|v| v.error_for_status())
+        .map_err(|err| format!("Download failed: {}", err))?;
+
+    let mut file = File::create(path).map_err(|err| format!("Couldn't create {}: {}", path.display(), err))?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = response.read(&mut buffer).map_err(|err| format!("Download failed: {}", err))?;
+        if read == 0 {
+            break;
+        }
+        file.write_all(&buffer[..read]).map_err(|err| format!("Couldn't write {}: {}", path.display(), err))?;
+        received.fetch_add(read as u64, Ordering::Relaxed);
+    }
+
+    Ok(())
+}
+
+//Counts bytes as reqwest reads them
+struct ProgressReader {
+    inner: File,
+    sent: Arc<AtomicU64>,
+}
+
+impl Read for ProgressReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.sent.fetch_add(read as u64, Ordering::Relaxed);
+        Ok(read)
+    }
+}
+
+//Marks everything up to the message as read
+pub fn ack(conn: &Connection, channel_id: &str, message_id: &str) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}/messages/{}/ack",
+        channel_id, message_id
+    );
+
+    let response = conn.client
+        .post(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(&serde_json::json!({ "token": null }))
+        .send()
+        .map_err(|err| format!("Couldn't mark channel as read: {}", err))?;
+    check_response(response, "Couldn't mark channel as read")
+}
+
+//Mutes or unmutes a whole guild, "@me" being DMs
+pub fn mute_guild(conn: &Connection, guild_id: &str, muted: bool) -> Result<GuildSettings, String> {
+    let body = serde_json::json!({ "muted": muted, "mute_config": null });
+    patch_guild_settings(conn, guild_id, &body)
+}
+
+pub fn mute_channel(
+    conn: &Connection,
+    guild_id: &str,
+    channel_id: &str,
+    muted: bool,
+) -> Result<GuildSettings, String> {
+    let body = serde_json::json!({
+        "channel_overrides": {
+            channel_id: { "muted": muted, "mute_config": null }
+        }
+    });
+    patch_guild_settings(conn, guild_id, &body)
+}
+
+//Discord answers with the whole updated settings object
+fn patch_guild_settings(
+    conn: &Connection,
+    guild_id: &str,
+    body: &serde_json::Value,
+) -> Result<GuildSettings, String> {
+    let url = format!(
+        "https://discord.com/api/v9/users/@me/guilds/{}/settings",
+        guild_id
+    );
+
+    let response = conn
+        .client
+        .patch(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(body)
+        .send()
+        .map_err(|err| format!("Couldn't update notification settings: {}", err))?;
+    if !response.status().is_success() {
+        return Err(error_message(response, "Couldn't update notification settings"));
+    }
+
+    let settings: serde_json::Value = response.json().map_err(|err| format!("Couldn't update notification settings: {}", err))?;
+    Ok(GuildSettings::from(&settings))
+}
+
+//Every bot command usable in a guild, or in a DM when guild_id is "@me"
+pub fn application_commands(conn: &Connection, guild_id: &str, channel_id: &str) -> Result<Vec<ApplicationCommand>, String> {
+    let url = match guild_id {
+        "@me" => format!(
+            "https://discord.com/api/v9/channels/{}/application-command-index",
+            channel_id
+        ),
+        guild_id => format!(
+            "https://discord.com/api/v9/guilds/{}/application-command-index",
+            guild_id
+        ),
+    };
+
+    let response = conn.client
+        .get(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .and_then(|v| v.error_for_status())
+        .map_err(|err| format!("Couldn't get bot commands: {}", err))?;
+    let index: serde_json::Value = response.json().map_err(|err| format!("Couldn't get bot commands: {}", err))?;
+
+    Ok(ApplicationCommand::from_index(&index))
+}
+
+//Runs a bot command, the reply shows up through the gateway like any message
+pub fn send_interaction(conn: &Connection, payload: &serde_json::Value) -> Result<(), String> {
+    let url = "https://discord.com/api/v9/interactions";
+    //The official client sends it as a form, attachments would go next to it
+    let form = Form::new().text("payload_json", payload.to_string());
+
+    let response = conn.client
+        .post(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .multipart(form)
+        .send()
+        .map_err(|err| format!("Command failed: {}", err))?;
+    check_response(response, "Command failed")
+}
+
+//Joining gets us notifications and puts the thread in our list on other clients
+pub fn join_thread(conn: &Connection, thread_id: &str) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}/thread-members/@me",
+        thread_id
+    );
+
+    let response = conn.client
+        .put(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .map_err(|err| format!("Couldn't join thread: {}", err))?;
+    check_response(response, "Couldn't join thread")
+}
+
+pub fn leave_thread(conn: &Connection, thread_id: &str) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}/thread-members/@me",
+        thread_id
+    );
+
+    let response = conn.client
+        .delete(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .map_err(|err| format!("Couldn't leave thread: {}", err))?;
+    check_response(response, "Couldn't leave thread")
+}
+
+//Sends an announcement to every channel following it
+pub fn crosspost(conn: &Connection, channel_id: &str, message_id: &str) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}/messages/{}/crosspost",
+        channel_id, message_id
+    );
+
+    let response = conn.client
+        .post(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .map_err(|err| format!("Couldn't publish message: {}", err))?;
+    check_response(response, "Couldn't publish message")
+}
+
+//Makes a webhook in target that receives everything published in channel
+pub fn follow_channel(conn: &Connection, channel_id: &str, target_id: &str) -> Result<(), String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}/followers",
+        channel_id
+    );
+
+    let response = conn.client
+        .post(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(&serde_json::json!({ "webhook_channel_id": target_id }))
+        .send()
+        .map_err(|err| format!("Couldn't follow channel: {}", err))?;
+    check_response(response, "Couldn't follow channel")
+}
+
+//Old forum posts and threads, active ones come through the gateway instead
+pub fn archived_threads(conn: &Connection, channel_id: &str) -> Result<Vec<Channel>, String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}/threads/archived/public?limit=50",
+        channel_id
+    );
+
+    let response = conn.client
+        .get(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .send()
+        .and_then(|v| v.error_for_status())
+        .map_err(|err| format!("Couldn't get posts: {}", err))?;
+    let body: serde_json::Value = response.json().map_err(|err| format!("Couldn't get posts: {}", err))?;
+
+    let threads = match body["threads"].as_array() {
+        Some(v) => v.iter().map(Channel::from).collect(),
+        None => Vec::new(),
+    };
+    Ok(threads)
+}
+
+//Starts a forum post, it comes back as the new thread
+pub fn create_post(conn: &Connection, forum_id: &str, title: &str, tags: &[String], body: &str) -> Result<Channel, String> {
+    let url = format!(
+        "https://discord.com/api/v9/channels/{}/threads",
+        forum_id
+    );
+    let payload = serde_json::json!({
+        "name": title,
+        "applied_tags": tags,
+        "message": { "content": body },
+    });
+
+    let response = conn.client
+        .post(url)
+        .header(&conn.auth.0, &conn.auth.1)
+        .json(&payload)
+        .send()
+        .map_err(|err| format!("Couldn't create post: {}", err))?;
+    if !response.status().is_success() {
+        return Err(error_message(response, "Couldn't create post"));
+    }
+
+    let thread: serde_json::Value = response.json().map_err(|err| format!("Couldn't create post: {}", err))?;
+    Ok(Channel::from(&thread))
+}
@@ -0,0 +1,122 @@
+//User settings, read from ~/.config/disrust/config.json
+//Anything missing from the file falls back to the defaults
+
+use serde::Deserialize;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub notifications: NotificationConfig,
+    pub input: InputConfig,
+    pub downloads: DownloadConfig,
+    pub links: LinkConfig,
+    pub clipboard: ClipboardConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationMethod {
+    //D-Bus if there is a session bus, terminal escapes otherwise
+    Auto,
+    Dbus,
+    //OSC 9/777 escape plus the bell
+    Osc,
+    Bell,
+    Off,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    pub mentions: bool,
+    pub dms: bool,
+    //Case insensitive, notifies when a message contains any of them
+    pub keywords: Vec<String>,
+    pub method: NotificationMethod,
+    //ids of guilds/channels that never notify
+    pub muted_guilds: Vec<String>,
+    pub muted_channels: Vec<String>,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> Self {
+        NotificationConfig {
+            mentions: true,
+            dms: true,
+            keywords: Vec::new(),
+            method: NotificationMethod::Auto,
+            muted_guilds: Vec::new(),
+            muted_channels: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct InputConfig {
+    //How tall the input box can grow before it scrolls instead
+    pub max_lines: u16,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        InputConfig { max_lines: 8 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DownloadConfig {
+    //~/Downloads when unset
+    pub dir: Option<String>,
+    //Gets the file path as its last argument, xdg-open (open on macOS) when unset
+    pub open_command: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LinkConfig {
+    //Gets the url as its last argument, xdg-open (open on macOS) when unset
+    pub browser: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ClipboardConfig {
+    //Gets the text on stdin, used instead of OSC 52 when set (wl-copy, xclip -sel clip, pbcopy)
+    pub command: Option<String>,
+}
+
+impl Config {
+    //$XDG_CONFIG_HOME/disrust, or ~/.config/disrust
+    pub fn dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(v) => PathBuf::from(v),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(base.join("disrust"))
+    }
+
+    //A missing file is fine, a broken one gets reported and ignored
+    pub fn load() -> Config {
+        let path = match Config::dir() {
+            Some(v) => v.join("config.json"),
+            None => return Config::default(),
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(v) => v,
+            Err(_) => return Config::default(),
+        };
+
+        match serde_json::from_str(&text) {
+            Ok(v) => v,
+            Err(err) => {
+                println!("Ignoring {}: {}", path.display(), err);
+                Config::default()
+            }
+        }
+    }
+}
@@ -1,7 +1,12 @@
 mod api;
+mod config;
 mod ui;
 use crate::api::data::*;
 use api::gateway_thread;
+use crossterm::event::{
+    DisableBracketedPaste, EnableBracketedPaste, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
 //has all the structs used
 use ui::{channels::App, chat_box::ChatBox, gui::run};
 
@@ -15,17 +20,31 @@ fn main() {
     token.pop(); //get rid of \n on the end
 
     let conn = Connection::new(&token);
+    let config = config::Config::load();
 
     let mut terminal = ratatui::init();
+    //Pastes arrive as one event instead of a key per char (and Enter per line)
+    //The enhancement flags let terminals that support them report Shift-Enter
+    let _ = crossterm::execute!(
+        std::io::stdout(),
+        EnableBracketedPaste,
+        PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+    );
 
     // conn.auth.1 is the token
-    let gate_rx = gateway_thread::start_thread(&conn.auth.1);
-    let guilds = gate_rx.recv().unwrap().guilds;
+    let (gate_rx, gate_tx) = gateway_thread::start_thread(&conn.auth.1);
+    let ready = gate_rx.recv().unwrap();
 
-    let mut app = App::new(guilds, conn);
+    let mut app = App::new(ready.guilds.clone(), conn, config, gate_tx);
+    app.react_to_gateway(&ready);
     let mut cbox = ChatBox::new();
     let result = run(&mut terminal, &mut app, &mut cbox, &gate_rx);
 
+    let _ = crossterm::execute!(
+        std::io::stdout(),
+        PopKeyboardEnhancementFlags,
+        DisableBracketedPaste
+    );
     ratatui::restore();
     if let Err(err) = result {
         println!("{:?}", err)
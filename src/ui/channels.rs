@@ -1,75 +1,2110 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::api::{data::*, wrapper};
+use serde_json::Value;
 
+use crate::api::{data::*, gateway_thread, wrapper};
+use crate::config::Config;
+
+use super::clipboard::{self, CopyTarget};
+use super::commands;
+use super::components::{self, ComponentFocus, SelectPicker};
+use super::download::{self, Download};
+use super::emoji::EmojiPicker;
+use super::file_picker::FilePicker;
+use super::forum;
+use super::hints::{HintAction, HintMode};
+use super::notifications;
+use super::opener;
+use super::search::SearchResults;
 use super::stateful_list::StatefulList;
+use super::switcher::{QuickSwitcher, SwitchTarget};
+use super::transfer;
+use super::upload::{self, Upload};
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum DisplayMode {
+    GuildMode,
+    ChannelMode,
+    FriendsMode,
+}
+
+//How long read markers wait before being sent, see send_acks
+const ACK_DELAY: Duration = Duration::from_secs(3);
+
+//How a guild or channel shows up in the sidebar
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReadStatus {
+    Read,
+    Unread,
+    //Number of pings waiting
+    Mentioned(i64),
+}
+
+pub struct App {
+    pub channels: StatefulList<Channel>,
+    pub guilds: StatefulList<Guild>,
+    pub dms: Vec<Channel>,
+    pub friends: StatefulList<Relationship>,
+    pub loaded_channels: HashMap<Channel, StatefulList<Msg>>,
+    //user id -> online/idle/dnd/offline
+    pub presences: HashMap<String, String>,
+    //channel id -> what we've read up to
+    pub read_states: HashMap<String, ReadState>,
+    //channel id -> newest message id we know of
+    pub last_messages: HashMap<String, String>,
+    //guild id ("@me" for DMs) -> mute and notification settings
+    pub guild_settings: HashMap<String, GuildSettings>,
+    pub show_members: bool,
+    //Rows of the member list for the channel we're subscribed to
+    pub member_list: Vec<MemberListItem>,
+    pub member_list_guild: String,
+    member_list_channel: String,
+    //Id of the list we're subscribed to, None until its first SYNC comes in
+    member_list_id: Option<String>,
+    //(guild id, user id) -> member, for nicknames and role colors
+    pub members: HashMap<(String, String), Member>,
+    //Guilds whose channel list was shown, so who's in voice there has names
+    voice_guilds: HashSet<String>,
+    //Shows usernames next to nicknames
+    pub show_usernames: bool,
+    //Open when it's Some
+    pub switcher: Option<QuickSwitcher>,
+    //Open while picking an emoji
+    pub emoji_picker: Option<EmojiPicker>,
+    //Open while browsing for files to attach
+    pub file_picker: Option<FilePicker>,
+    //Only one at a time
+    pub upload: Option<Upload>,
+    pub download: Option<Download>,
+    //Open the downloaded files once they're saved
+    open_download: bool,
+    //Labels on the links on screen while picking one
+    pub hints: Option<HintMode>,
+    //Which messages of the open channel fit on screen last draw
+    pub visible_messages: Range<usize>,
+    //Open while choosing what to copy
+    pub copy_menu: bool,
+    //Set while moving between a message's buttons and menus
+    pub component_focus: Option<ComponentFocus>,
+    //Open while picking from a bot's select menu
+    pub select_picker: Option<SelectPicker>,
+    //forum id -> its archived posts, fetched once
+    archived_posts: HashMap<String, Vec<Channel>>,
+    //Posts of the forum on screen
+    pub forum_posts: StatefulList<Channel>,
+    //Open after /search
+    pub search: Option<SearchResults>,
+    //Open after /help
+    pub show_help: bool,
+    //Id of what the first /leave asked about, the second one goes through
+    leave_armed: Option<String>,
+    //Same for d in the friends list, holds the user id
+    remove_armed: Option<String>,
+    //Last thing that happened worth telling, cleared on the next key press
+    pub status: Option<String>,
+    //channel id -> newest message we read there, waiting to be acked
+    pending_acks: HashMap<String, String>,
+    last_ack: Instant,
+    //Last visited channels, newest first
+    pub recent: Vec<Channel>,
+    pub user: User,
+    //Gateway session, sent along with interactions
+    pub session_id: String,
+    //guild id (channel id for DMs) -> the bot commands usable there
+    pub app_commands: HashMap<String, Vec<ApplicationCommand>>,
+    pub mode: DisplayMode,
+    pub conn: Connection,
+    pub config: Config,
+    //For sending payloads through the gateway
+    pub gate_tx: Sender<Value>,
+}
+
+impl App {
+    //build new app
+    pub fn new(
+        guilds: Vec<Guild>,
+        conn: Connection,
+        config: Config,
+        gate_tx: Sender<Value>,
+    ) -> App {
+        let mut last_messages = HashMap::new();
+        for guild in &guilds {
+            for channel in &guild.channels {
+                last_messages.insert(channel.id.clone(), channel.last_message_id.clone());
+            }
+        }
+
+        App {
+            channels: StatefulList::from(Vec::new()),
+            guilds: StatefulList::from(guilds),
+            dms: Vec::new(),
+            friends: StatefulList::from(Vec::new()),
+            loaded_channels: HashMap::new(),
+            presences: HashMap::new(),
+            read_states: HashMap::new(),
+            last_messages,
+            guild_settings: HashMap::new(),
+            show_members: false,
+            member_list: Vec::new(),
+            member_list_guild: String::new(),
+            member_list_channel: String::new(),
+            member_list_id: None,
+            members: HashMap::new(),
+            voice_guilds: HashSet::new(),
+            show_usernames: false,
+            switcher: None,
+            emoji_picker: None,
+            file_picker: None,
+            upload: None,
+            download: None,
+            open_download: false,
+            status: None,
+            pending_acks: HashMap::new(),
+            last_ack: Instant::now(),
+            hints: None,
+            copy_menu: false,
+            component_focus: None,
+            select_picker: None,
+            archived_posts: HashMap::new(),
+            forum_posts: StatefulList::from(Vec::new()),
+            search: None,
+            show_help: false,
+            leave_armed: None,
+            remove_armed: None,
+            visible_messages: 0..0,
+            recent: Vec::new(),
+            user: User::new(),
+            session_id: String::new(),
+            app_commands: HashMap::new(),
+            mode: DisplayMode::GuildMode,
+            conn,
+            config,
+            gate_tx,
+        }
+    }
+
+    //Very awkward
+    //Use enums and strum crate
+    pub fn react_to_gateway(&mut self, gate_response: &GatewayResponse) {
+        match gate_response.operation.as_str() {
+            "MESSAGE_CREATE" => {
+                let mut channel_found = Vec::new();
+                let gate_channel_id = &gate_response.message.channel_id;
+                for key in self.loaded_channels.keys() {
+                    // println!("current channel id: {}, looking for: {}", &key.id, gate_channel_id);
+                    let channel_id = &key.id;
+                    if channel_id == gate_channel_id {
+                        channel_found.push(key.clone());
+                    }
+                }
+
+                for key in channel_found {
+                    let mut old_messages = self.loaded_channels[&key].clone();
+                    old_messages.items.push(gate_response.message.clone());
+                    //updates the messages with the new one
+                    self.loaded_channels.insert(key, old_messages);
+                    // dbg!(&self.loaded_channels);
+                }
+
+                let message = &gate_response.message;
+                if let (Some(guild_id), Some(member)) = (&message.guild_id, &message.member) {
+                    self.cache_member(guild_id, member);
+                }
+
+                self.count_thread_reply(&message.channel_id);
+                self.track_unread(message);
+            }
+            "THREAD_CREATE" | "THREAD_UPDATE" => {
+                if let Some(thread) = &gate_response.channel {
+                    self.update_thread(thread.clone());
+                }
+            }
+            "THREAD_DELETE" => {
+                if let Some(thread) = &gate_response.channel {
+                    if let Some(guild) = self.guild_mut(&thread.guild_id) {
+                        guild.remove_channel(&thread.id);
+                    }
+                    self.refresh_channels(&thread.guild_id);
+                }
+            }
+            "THREAD_LIST_SYNC" => {
+                if let Some(sync) = &gate_response.thread_sync {
+                    self.sync_threads(sync);
+                }
+            }
+            "THREAD_MEMBERS" => {
+                if let Some(update) = &gate_response.thread_members {
+                    if update.added.contains(&self.user.id) {
+                        self.set_thread_joined(&update.thread_id, true);
+                    } else if update.removed.contains(&self.user.id) {
+                        self.set_thread_joined(&update.thread_id, false);
+                    }
+                }
+            }
+            "READY" => {
+                for presence in &gate_response.presences {
+                    self.presences
+                        .insert(presence.user_id.clone(), presence.status.clone());
+                }
+
+                self.user = gate_response.user.clone();
+                self.session_id = gate_response.session_id.clone();
+                self.dms = gate_response.dms.clone();
+                for dm in &self.dms {
+                    self.last_messages
+                        .insert(dm.id.clone(), dm.last_message_id.clone());
+                }
+                for read_state in &gate_response.read_states {
+                    self.read_states
+                        .insert(read_state.channel_id.clone(), read_state.clone());
+                }
+                for settings in &gate_response.guild_settings {
+                    self.guild_settings
+                        .insert(settings.guild_id.clone(), settings.clone());
+                }
+                //Channels without a read state are treated as read
+                //otherwise every server we never opened would light up
+                for (channel_id, last_message_id) in &self.last_messages {
+                    self.read_states
+                        .entry(channel_id.clone())
+                        .or_insert(ReadState {
+                            channel_id: channel_id.clone(),
+                            last_read_id: last_message_id.clone(),
+                            mention_count: 0,
+                        });
+                }
+            }
+            "MESSAGE_UPDATE" => {
+                let update = &gate_response.message;
+                for (channel, messages) in self.loaded_channels.iter_mut() {
+                    if channel.id != update.channel_id {
+                        continue;
+                    }
+                    for message in messages.items.iter_mut() {
+                        if message.id == update.id {
+                            //Reactions are kept up to date by their own events
+                            let reactions = std::mem::take(&mut message.reactions);
+                            *message = Msg {
+                                reactions,
+                                ..update.clone()
+                            };
+                        }
+                    }
+                }
+            }
+            "INTERACTION_FAILURE" => {
+                self.status = Some("The application did not respond".to_string());
+            }
+            "DISCONNECTED" => {
+                self.status = Some("Disconnected from discord, restart to reconnect".to_string());
+            }
+            "MESSAGE_REACTION" => {
+                if let Some(update) = &gate_response.reaction {
+                    let we = self.user.id.clone();
+                    for (channel, messages) in self.loaded_channels.iter_mut() {
+                        if channel.id != update.channel_id {
+                            continue;
+                        }
+                        for message in messages.items.iter_mut() {
+                            if message.id == update.message_id {
+                                message.apply_reaction(update, &we);
+                            }
+                        }
+                    }
+                }
+            }
+            "USER_GUILD_SETTINGS_UPDATE" => {
+                for settings in &gate_response.guild_settings {
+                    self.guild_settings
+                        .insert(settings.guild_id.clone(), settings.clone());
+                }
+            }
+            "MESSAGE_ACK" => {
+                for read_state in &gate_response.read_states {
+                    self.read_states
+                        .insert(read_state.channel_id.clone(), read_state.clone());
+                }
+            }
+            "PRESENCE_UPDATE" => {
+                for presence in &gate_response.presences {
+                    self.presences
+                        .insert(presence.user_id.clone(), presence.status.clone());
+
+                    for friend in self.friends.items.iter_mut() {
+                        if friend.user.id == presence.user_id {
+                            friend.status = presence.status.clone();
+                        }
+                    }
+
+                    for item in self.member_list.iter_mut() {
+                        if let MemberListItem::Member(member) = item {
+                            if member.user.id == presence.user_id {
+                                member.status = presence.status.clone();
+                            }
+                        }
+                    }
+                }
+            }
+            "GUILD_MEMBER_LIST_UPDATE" => {
+                if let Some(update) = &gate_response.member_list {
+                    let ours = match &self.member_list_id {
+                        Some(id) => *id == update.id,
+                        None => update.has_sync(),
+                    };
+                    if ours && update.guild_id == self.member_list_guild {
+                        self.member_list_id = Some(update.id.clone());
+                        update.apply(&mut self.member_list);
+
+                        let guild_id = self.member_list_guild.clone();
+                        for member in update.members() {
+                            self.cache_member(&guild_id, member);
+                        }
+                    }
+                }
+            }
+            "VOICE_STATE_UPDATE" => {
+                if let Some(state) = &gate_response.voice_state {
+                    if let Some(member) = &state.member {
+                        self.cache_member(&state.guild_id, member);
+                    }
+                    if let Some(guild) = self.guild_mut(&state.guild_id) {
+                        guild.update_voice_state(state.clone());
+                    }
+                }
+            }
+            "GUILD_VOICE_STATES" => {
+                if let Some(sync) = &gate_response.voice_sync {
+                    if let Some(guild) = self.guild_mut(&sync.guild_id) {
+                        guild.voice_states = sync.states.clone();
+                    }
+                    if self.voice_guilds.contains(&sync.guild_id) {
+                        self.request_voice_members(&sync.guild_id);
+                    }
+                }
+            }
+            "GUILD_MEMBERS_CHUNK" => {
+                if let Some(chunk) = &gate_response.members_chunk {
+                    for member in &chunk.members {
+                        self.cache_member(&chunk.guild_id, member);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    //Updates unread/mention state for a message that just came in
+    fn track_unread(&mut self, message: &Msg) {
+        self.last_messages
+            .insert(message.channel_id.clone(), message.id.clone());
+
+        if let Some(channel) = self.viewed_channel() {
+            if channel.id == message.channel_id && message.user.id != self.user.id {
+                self.mark_read(&channel);
+                return;
+            }
+        }
+
+        let is_dm = message.guild_id.is_none();
+        let mentioned = self.mentions_us(message);
+
+        let read_state = self
+            .read_states
+            .entry(message.channel_id.clone())
+            .or_insert(ReadState {
+                channel_id: message.channel_id.clone(),
+                last_read_id: String::new(),
+                mention_count: 0,
+            });
+
+        //Our own messages never count as unread
+        if message.user.id == self.user.id {
+            read_state.last_read_id = message.id.clone();
+            read_state.mention_count = 0;
+            return;
+        }
+
+        //Every DM counts as a ping
+        if is_dm || mentioned {
+            read_state.mention_count += 1;
+        }
+
+        self.notify_message(message);
+    }
+
+    //Pops a notification for pings, DMs and keywords unless it's muted
+    fn notify_message(&self, message: &Msg) {
+        let config = &self.config.notifications;
+
+        let is_dm = message.guild_id.is_none();
+        let guild_id = message.guild_id.as_deref().unwrap_or("@me");
+        let mentioned = self.mentions_us(message);
+        let content = message.content.to_lowercase();
+        let keyword = config
+            .keywords
+            .iter()
+            .any(|word| !word.is_empty() && content.contains(&word.to_lowercase()));
+
+        let wanted = match self.notification_level(guild_id, &message.channel_id) {
+            NotificationLevel::Nothing => false,
+            NotificationLevel::All if !is_dm => true,
+            _ => (is_dm && config.dms) || (mentioned && config.mentions),
+        };
+        if !(wanted || keyword) || self.is_muted(guild_id, &message.channel_id) {
+            return;
+        }
+
+        let title = match self.find_channel(&message.channel_id) {
+            Some((guild, channel)) => {
+                format!(
+                    "{} in #{} ({})",
+                    message.user.name, channel.name, guild.name
+                )
+            }
+            None => message.user.name.clone(),
+        };
+        notifications::notify(config, &title, &message.content);
+    }
+
+    //Pinged directly, through one of our roles, or through @everyone when the guild doesn't suppress it
+    fn mentions_us(&self, message: &Msg) -> bool {
+        let guild_id = message.guild_id.as_deref().unwrap_or("@me");
+        let suppress_everyone = match self.guild_settings.get(guild_id) {
+            Some(settings) => settings.suppress_everyone,
+            None => false,
+        };
+        let role_pinged = match self.guilds.items.iter().find(|guild| guild.id == guild_id) {
+            Some(guild) => message
+                .mention_roles
+                .iter()
+                .any(|role| guild.own_roles.contains(role)),
+            None => false,
+        };
+
+        message.mentions.contains(&self.user.id)
+            || role_pinged
+            || (message.mention_everyone && !suppress_everyone)
+    }
+
+    //Muted either in our config or on discord
+    pub fn is_muted(&self, guild_id: &str, channel_id: &str) -> bool {
+        let config = &self.config.notifications;
+
+        let config_muted = config.muted_guilds.iter().any(|muted| muted == guild_id)
+            || config
+                .muted_channels
+                .iter()
+                .any(|muted| muted == channel_id);
+
+        config_muted
+            || self.discord_guild_muted(guild_id)
+            || self.discord_channel_muted(guild_id, channel_id)
+    }
+
+    pub fn guild_muted(&self, guild: &Guild) -> bool {
+        let config = &self.config.notifications;
+        config.muted_guilds.contains(&guild.id) || self.discord_guild_muted(&guild.id)
+    }
+
+    pub fn channel_muted(&self, channel: &Channel) -> bool {
+        self.is_muted(&channel.guild_id, &channel.id)
+    }
+
+    fn discord_guild_muted(&self, guild_id: &str) -> bool {
+        match self.guild_settings.get(guild_id) {
+            Some(settings) => settings.muted,
+            None => false,
+        }
+    }
+
+    fn discord_channel_muted(&self, guild_id: &str, channel_id: &str) -> bool {
+        let settings = match self.guild_settings.get(guild_id) {
+            Some(v) => v,
+            None => return false,
+        };
+
+        match settings.channel_override(channel_id) {
+            Some(channel) => channel.muted,
+            None => false,
+        }
+    }
+
+    //Channel override wins over the guild, which wins over the server default
+    fn notification_level(&self, guild_id: &str, channel_id: &str) -> NotificationLevel {
+        let default = match self.guilds.items.iter().find(|v| v.id == guild_id) {
+            Some(guild) => guild.default_notifications,
+            None => NotificationLevel::Mentions,
+        };
+        let settings = match self.guild_settings.get(guild_id) {
+            Some(v) => v,
+            None => return default,
+        };
+
+        if let Some(channel) = settings.channel_override(channel_id) {
+            if channel.notification_level != NotificationLevel::Inherit {
+                return channel.notification_level;
+            }
+        }
+
+        match settings.notification_level {
+            NotificationLevel::Inherit => default,
+            level => level,
+        }
+    }
+
+    fn cache_member(&mut self, guild_id: &str, member: &Member) {
+        let key = (guild_id.to_string(), member.user.id.clone());
+        self.members.insert(key, member.clone());
+    }
+
+    //Name and role color to show for a message author
+    pub fn author_name(&self, message: &Msg, guild_id: &str) -> (String, Option<u32>) {
+        let key = (guild_id.to_string(), message.user.id.clone());
+        let member = match self.members.get(&key) {
+            Some(v) => Some(v),
+            None => message.member.as_ref(),
+        };
+
+        let (name, color) = match member {
+            Some(member) => {
+                let guild = self.guilds.items.iter().find(|guild| guild.id == guild_id);
+                let color = guild.and_then(|guild| member.color(guild));
+                (member.display_name().to_string(), color)
+            }
+            None => (message.user.display_name().to_string(), None),
+        };
+
+        if self.show_usernames && name != message.user.name {
+            return (format!("{} ({})", name, message.user.name), color);
+        }
+
+        (name, color)
+    }
+
+    //Whoever is in the voice channel, with names to show
+    pub fn voice_members(&self, channel: &Channel) -> Vec<(String, VoiceState)> {
+        let guild = match self.guilds.items.iter().find(|v| v.id == channel.guild_id) {
+            Some(v) => v,
+            None => return Vec::new(),
+        };
+
+        guild
+            .voice_states_in(&channel.id)
+            .into_iter()
+            .map(|state| {
+                let key = (guild.id.clone(), state.user_id.clone());
+                let name = match self.members.get(&key).or(state.member.as_ref()) {
+                    Some(member) => member.display_name().to_string(),
+                    None => "Someone".to_string(),
+                };
+                (name, state.clone())
+            })
+            .collect()
+    }
+
+    //READY and GUILD_CREATE voice states only have user ids, one op 8 for the whole guild
+    fn request_voice_members(&mut self, guild_id: &str) {
+        let guild = match self.guilds.items.iter().find(|v| v.id == guild_id) {
+            Some(v) => v,
+            None => return,
+        };
+        let user_ids: Vec<String> = guild
+            .voice_states
+            .iter()
+            .filter(|state| {
+                !self
+                    .members
+                    .contains_key(&(guild_id.to_string(), state.user_id.clone()))
+            })
+            .map(|state| state.user_id.clone())
+            .collect();
+
+        if !user_ids.is_empty() {
+            let request = gateway_thread::request_members(guild_id, &user_ids);
+            self.send_to_gateway(request);
+        }
+    }
+
+    pub fn toggle_usernames(&mut self) {
+        self.show_usernames = !self.show_usernames;
+    }
+
+    //Messages from REST have no member attached, so ask the gateway for them
+    fn request_authors(&mut self, channel: &Channel) {
+        if channel.guild_id == "@me" {
+            return;
+        }
+        let messages = match self.loaded_channels.get(channel) {
+            Some(v) => v,
+            None => return,
+        };
+
+        let mut user_ids: Vec<String> = Vec::new();
+        for message in &messages.items {
+            let key = (channel.guild_id.clone(), message.user.id.clone());
+            if !self.members.contains_key(&key) && !user_ids.contains(&message.user.id) {
+                user_ids.push(message.user.id.clone());
+            }
+        }
+
+        if !user_ids.is_empty() {
+            let request = gateway_thread::request_members(&channel.guild_id, &user_ids);
+            self.send_to_gateway(request);
+        }
+    }
+
+    pub fn toggle_members(&mut self) {
+        self.show_members = !self.show_members;
+        if self.show_members {
+            self.subscribe_members();
+        }
+    }
+
+    //Asks the gateway for the member list of the open channel (op 14)
+    //Discord then keeps it updated through GUILD_MEMBER_LIST_UPDATE
+    fn subscribe_members(&mut self) {
+        let channel = match self.viewed_channel() {
+            Some(v) => v,
+            None => return,
+        };
+        //DMs don't have a member list
+        if channel.guild_id == "@me" || channel.id == self.member_list_channel {
+            return;
+        }
+
+        self.member_list.clear();
+        self.member_list_guild = channel.guild_id.clone();
+        self.member_list_channel = channel.id.clone();
+        //Ops for the old list can still be on the way, they'd be applied to the wrong rows
+        self.member_list_id = None;
+
+        let request = gateway_thread::lazy_guild_request(&channel.guild_id, &channel.id);
+        self.send_to_gateway(request);
+    }
+
+    //The gateway thread stops once the connection drops, nothing goes through after that
+    fn send_to_gateway(&mut self, request: Value) {
+        if self.gate_tx.send(request).is_err() {
+            self.status = Some("Disconnected from discord, restart to reconnect".to_string());
+        }
+    }
+
+    //Flips the discord mute on the selected guild or the open channel
+    pub fn toggle_mute(&mut self) {
+        //Discord's setting can't undo our config, say so instead of silently doing nothing
+        if let Some(reason) = self.config_mute() {
+            self.status = Some(format!(
+                "Muted by {} in config.json, unmute it there",
+                reason
+            ));
+            return;
+        }
+
+        let settings = match self.mode {
+            DisplayMode::GuildMode => {
+                let guild = self.get_guild();
+                let muted = !self.discord_guild_muted(&guild.id);
+                wrapper::mute_guild(&self.conn, &guild.id, muted)
+            }
+            DisplayMode::ChannelMode => {
+                let channel = match self.viewed_channel() {
+                    Some(v) => v,
+                    None => return,
+                };
+                let muted = !self.discord_channel_muted(&channel.guild_id, &channel.id);
+                wrapper::mute_channel(&self.conn, &channel.guild_id, &channel.id, muted)
+            }
+            DisplayMode::FriendsMode => return,
+        };
+
+        match settings {
+            Ok(settings) => {
+                self.guild_settings
+                    .insert(settings.guild_id.clone(), settings);
+            }
+            Err(err) => self.status = Some(err),
+        }
+    }
+
+    //Which config list mutes what m would toggle, if any
+    fn config_mute(&mut self) -> Option<&'static str> {
+        let (guild_id, channel_id) = match self.mode {
+            DisplayMode::GuildMode => (self.get_guild().id, None),
+            DisplayMode::ChannelMode => {
+                let channel = self.viewed_channel()?;
+                (channel.guild_id, Some(channel.id))
+            }
+            DisplayMode::FriendsMode => return None,
+        };
+
+        let config = &self.config.notifications;
+        if config.muted_guilds.contains(&guild_id) {
+            return Some("muted_guilds");
+        }
+        match channel_id {
+            Some(id) if config.muted_channels.contains(&id) => Some("muted_channels"),
+            _ => None,
+        }
+    }
+
+    //Looks a channel up across every guild
+    fn find_channel(&self, channel_id: &str) -> Option<(&Guild, &Channel)> {
+        for guild in &self.guilds.items {
+            for channel in guild.channels.iter().chain(&guild.posts) {
+                if channel.id == channel_id {
+                    return Some((guild, channel));
+                }
+            }
+        }
+
+        None
+    }
+
+    //"#general (Server)" or "@friend", for showing where a message is from
+    pub fn channel_label(&self, channel_id: &str) -> String {
+        if let Some((guild, channel)) = self.find_channel(channel_id) {
+            return format!("#{} ({})", channel.name, guild.name);
+        }
+        match self.dms.iter().find(|dm| dm.id == channel_id) {
+            Some(dm) => format!("@{}", dm.name),
+            None => String::new(),
+        }
+    }
+
+    //Acks the channel if there is anything new in it
+    pub fn mark_read(&mut self, channel: &Channel) {
+        let latest = match self.last_messages.get(&channel.id) {
+            Some(v) if !v.is_empty() => v.clone(),
+            _ => return,
+        };
+
+        let read_state = self
+            .read_states
+            .entry(channel.id.clone())
+            .or_insert(ReadState {
+                channel_id: channel.id.clone(),
+                last_read_id: String::new(),
+                mention_count: 0,
+            });
+
+        if snowflake_newer(&latest, &read_state.last_read_id) || read_state.mention_count > 0 {
+            read_state.last_read_id = latest.clone();
+            read_state.mention_count = 0;
+            self.pending_acks.insert(channel.id.clone(), latest);
+        }
+    }
+
+    //Acks go out at most every ACK_DELAY and only for the newest message of each channel
+    //A busy channel would otherwise send one per message
+    pub fn send_acks(&mut self) {
+        if self.pending_acks.is_empty() || self.last_ack.elapsed() < ACK_DELAY {
+            return;
+        }
+        self.last_ack = Instant::now();
+        let acks = std::mem::take(&mut self.pending_acks);
+        let conn = self.conn.clone();
+        //A failed ack only leaves other clients showing it unread, the next one fixes that
+        thread::spawn(move || {
+            for (channel_id, message_id) in acks {
+                let _ = wrapper::ack(&conn, &channel_id, &message_id);
+            }
+        });
+    }
+
+    //On quit, waits for them since the process is about to end
+    pub fn flush_acks(&mut self) {
+        for (channel_id, message_id) in std::mem::take(&mut self.pending_acks) {
+            let _ = wrapper::ack(&self.conn, &channel_id, &message_id);
+        }
+    }
+
+    pub fn channel_unread(&self, channel: &Channel) -> ReadStatus {
+        let read_state = match self.read_states.get(&channel.id) {
+            Some(v) => v,
+            None => return ReadStatus::Read,
+        };
+
+        if read_state.mention_count > 0 {
+            return ReadStatus::Mentioned(read_state.mention_count);
+        }
+
+        //Muted channels only ever show pings
+        if self.channel_muted(channel) {
+            return ReadStatus::Read;
+        }
+
+        match self.last_messages.get(&channel.id) {
+            Some(latest) if snowflake_newer(latest, &read_state.last_read_id) => ReadStatus::Unread,
+            _ => ReadStatus::Read,
+        }
+    }
+
+    //A guild is as unread as its channels, mentions get added up
+    pub fn guild_unread(&self, guild: &Guild) -> ReadStatus {
+        let mut unread = ReadStatus::Read;
+        for channel in &guild.channels {
+            unread = match (unread, self.channel_unread(channel)) {
+                (ReadStatus::Mentioned(a), ReadStatus::Mentioned(b)) => {
+                    ReadStatus::Mentioned(a + b)
+                }
+                (ReadStatus::Mentioned(a), _) | (_, ReadStatus::Mentioned(a)) => {
+                    ReadStatus::Mentioned(a)
+                }
+                (ReadStatus::Unread, _) | (_, ReadStatus::Unread) => ReadStatus::Unread,
+                _ => ReadStatus::Read,
+            };
+        }
+
+        unread
+    }
+
+    //The channel whose messages are on screen, if any
+    pub fn viewed_channel(&self) -> Option<Channel> {
+        match self.mode {
+            DisplayMode::ChannelMode => {
+                let index = self.channels.state.selected()?;
+                self.channels.items.get(index).cloned()
+            }
+            _ => None,
+        }
+    }
+
+    //Guild of the channel on screen, None in DMs
+    pub fn viewed_guild(&self) -> Option<&Guild> {
+        let channel = self.viewed_channel()?;
+        self.guilds
+            .items
+            .iter()
+            .find(|guild| guild.id == channel.guild_id)
+    }
+
+    pub fn enter_guild(&mut self) {
+        let current_guild = self.get_guild();
+        let channels = current_guild.channels;
+
+        self.channels = StatefulList::from(channels);
+        self.mode = DisplayMode::ChannelMode;
+        //Asking for every guild at once on READY gets the gateway closed
+        if self.voice_guilds.insert(current_guild.id.clone()) {
+            self.request_voice_members(&current_guild.id);
+        }
+    }
+
+    pub fn leave_guild(&mut self) {
+        self.mode = DisplayMode::GuildMode;
+    }
+
+    pub fn open_switcher(&mut self) {
+        self.switcher = Some(QuickSwitcher::new());
+        self.update_switcher();
+    }
+
+    pub fn close_switcher(&mut self) {
+        self.switcher = None;
+    }
+
+    //Reruns the search after the query changed
+    pub fn update_switcher(&mut self) {
+        let targets = self.switch_targets();
+        if let Some(switcher) = self.switcher.as_mut() {
+            switcher.update(targets, &self.recent);
+        }
+    }
+
+    pub fn open_emoji_picker(&mut self) {
+        self.emoji_picker = Some(EmojiPicker::new(None));
+        self.update_emoji_picker();
+    }
+
+    pub fn open_file_picker(&mut self) {
+        self.file_picker = Some(FilePicker::new());
+    }
+
+    pub fn close_file_picker(&mut self) {
+        self.file_picker = None;
+    }
+
+    //Sends the files to the open channel, with the caption as the message
+    //Returns false if it couldn't start, the files should stay attached then
+    pub fn send_attachments(&mut self, caption: String, files: Vec<PathBuf>) -> bool {
+        if let Some(upload) = &self.upload {
+            self.status = Some(format!("Still uploading {}", upload.label));
+            return false;
+        }
+
+        let channel_id = self.get_channel().id;
+        let limit = upload::upload_limit(&self.user);
+        match upload::start(&self.conn, &channel_id, caption, files, limit) {
+            Ok(v) => {
+                self.upload = Some(v);
+                true
+            }
+            Err(err) => {
+                self.status = Some(err);
+                false
+            }
+        }
+    }
+
+    //Checks on uploads and downloads, call every loop
+    pub fn poll_transfers(&mut self) {
+        if let Some((label, result)) = transfer::finished(&mut self.upload) {
+            self.status = match result {
+                Ok(()) => Some(format!("Uploaded {}", label)),
+                Err(err) => Some(err),
+            };
+        }
+
+        if let Some((label, result)) = transfer::finished(&mut self.download) {
+            self.status = match result {
+                Ok(paths) => {
+                    if self.open_download {
+                        for path in &paths {
+                            download::open(&self.config.downloads, path);
+                        }
+                    }
+                    let dir = download::download_dir(&self.config.downloads);
+                    Some(format!("Saved {} to {}", label, dir.display()))
+                }
+                Err(err) => Some(err),
+            };
+        }
+    }
+
+    //Labels every link on screen, typing a label opens or copies it
+    pub fn start_hints(&mut self, action: HintAction) {
+        let messages = match self.viewed_messages() {
+            Some(v) => v.items.clone(),
+            None => return,
+        };
+        let end = self.visible_messages.end.min(messages.len());
+        let start = self.visible_messages.start.min(end);
+
+        self.hints = HintMode::new(&messages[start..end], action);
+        if self.hints.is_none() {
+            self.status = Some("No links on screen".to_string());
+        }
+    }
+
+    pub fn hint_input(&mut self, c: char) {
+        let hints = match self.hints.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
+        hints.typed.push(c);
+
+        if let Some(hint) = hints.get_match() {
+            let url = hint.url.clone();
+            match hints.action {
+                HintAction::Open => {
+                    opener::open_with(self.config.links.browser.as_deref(), &url);
+                    self.status = Some(format!("Opened {}", url));
+                }
+                HintAction::Copy => {
+                    clipboard::copy(&self.config.clipboard, &url);
+                    self.status = Some(format!("Copied {}", url));
+                }
+            }
+            self.hints = None;
+        } else if !hints.any_left() {
+            //Typo, start over
+            hints.typed.clear();
+        }
+    }
+
+    pub fn close_hints(&mut self) {
+        self.hints = None;
+    }
+
+    pub fn open_copy_menu(&mut self) {
+        if self.viewed_channel().is_some() {
+            self.copy_menu = true;
+        }
+    }
+
+    pub fn close_copy_menu(&mut self) {
+        self.copy_menu = false;
+    }
+
+    //Everything but the channel id comes from the selected message
+    pub fn copy(&mut self, target: CopyTarget) {
+        self.copy_menu = false;
+        let channel = match self.viewed_channel() {
+            Some(v) => v,
+            None => return,
+        };
+        let message = self.selected_message();
+
+        let text = match (target, message) {
+            (CopyTarget::ChannelId, _) => channel.id,
+            (_, None) => {
+                self.status = Some("Select a message first (j/k)".to_string());
+                return;
+            }
+            (CopyTarget::Content, Some(message)) => message.content,
+            (CopyTarget::AuthorId, Some(message)) => message.user.id,
+            //guild_id is @me for DMs, same as discord's own links
+            (CopyTarget::Link, Some(message)) => format!(
+                "https://discord.com/channels/{}/{}/{}",
+                channel.guild_id, channel.id, message.id
+            ),
+        };
+
+        clipboard::copy(&self.config.clipboard, &text);
+        let preview: String = text.chars().take(40).collect();
+        self.status = Some(format!("Copied {}", preview.replace('\n', " ")));
+    }
+
+    //Saves the selected message's attachments, then opens them if open is set
+    pub fn download_selected(&mut self, open: bool) {
+        let attachments = match self.selected_message() {
+            Some(v) => v.attachments,
+            None => {
+                self.status = Some("Select a message first (j/k)".to_string());
+                return;
+            }
+        };
+        if attachments.is_empty() {
+            self.status = Some("That message has no attachments".to_string());
+            return;
+        }
+        if let Some(download) = &self.download {
+            self.status = Some(format!("Still downloading {}", download.label));
+            return;
+        }
+
+        match download::start(&self.conn, &self.config.downloads, attachments) {
+            Ok(v) => {
+                self.download = Some(v);
+                self.open_download = open;
+            }
+            Err(err) => self.status = Some(err),
+        }
+    }
+
+    //What goes in the corner of the input box
+    pub fn get_status(&self) -> Option<String> {
+        if let Some(upload) = &self.upload {
+            return Some(upload.progress());
+        }
+        if let Some(download) = &self.download {
+            return Some(download.progress());
+        }
+        self.status.clone()
+    }
+
+    //Same picker, but the emoji goes on the selected message
+    pub fn react_to_selected(&mut self) {
+        if let Some(message) = self.selected_message() {
+            self.emoji_picker = Some(EmojiPicker::new(Some(message)));
+            self.update_emoji_picker();
+        }
+    }
+
+    //Adds our reaction, or takes it back if it's already there
+    pub fn toggle_reaction(&mut self, message: &Msg, emoji: &ReactionEmoji) {
+        //The gateway echoes it back, that's what updates the counts
+        let reacted = self
+            .find_message(&message.channel_id, &message.id)
+            .unwrap_or(message)
+            .reactions
+            .iter()
+            .any(|reaction| reaction.emoji == *emoji && reaction.me);
+
+        let result = match reacted {
+            true => wrapper::remove_reaction(&self.conn, &message.channel_id, &message.id, emoji),
+            false => wrapper::add_reaction(&self.conn, &message.channel_id, &message.id, emoji),
+        };
+        if let Err(err) = result {
+            self.status = Some(err);
+        }
+    }
+
+    fn find_message(&self, channel_id: &str, message_id: &str) -> Option<&Msg> {
+        let (_, messages) = self
+            .loaded_channels
+            .iter()
+            .find(|(channel, _)| channel.id == channel_id)?;
+        messages
+            .items
+            .iter()
+            .find(|message| message.id == message_id)
+    }
+
+    //Moves the message selection, it starts at the newest message
+    //In forums it goes through the posts instead, newest at the top
+    pub fn select_older_message(&mut self) {
+        if self.viewed_forum().is_some() {
+            if !self.forum_posts.items.is_empty() {
+                self.forum_posts.next();
+            }
+            return;
+        }
+        if let Some(messages) = self.viewed_messages() {
+            match messages.state.selected() {
+                Some(_) => messages.previous(),
+                None => messages.state.select(Some(messages.items.len() - 1)),
+            }
+        }
+    }
+
+    pub fn select_newer_message(&mut self) {
+        if self.viewed_forum().is_some() {
+            if !self.forum_posts.items.is_empty() {
+                self.forum_posts.previous();
+            }
+            return;
+        }
+        if let Some(messages) = self.viewed_messages() {
+            match messages.state.selected() {
+                Some(_) => messages.next(),
+                None => messages.state.select(Some(messages.items.len() - 1)),
+            }
+        }
+    }
+
+    pub fn selected_message(&mut self) -> Option<Msg> {
+        let messages = self.viewed_messages()?;
+        let index = messages.state.selected()?;
+        messages.items.get(index).cloned()
+    }
+
+    //Messages of the channel on screen, None if there aren't any
+    fn viewed_messages(&mut self) -> Option<&mut StatefulList<Msg>> {
+        let channel = self.viewed_channel()?;
+        let messages = self.loaded_channels.get_mut(&channel)?;
+        match messages.items.is_empty() {
+            true => None,
+            false => Some(messages),
+        }
+    }
+
+    pub fn close_emoji_picker(&mut self) {
+        self.emoji_picker = None;
+    }
+
+    pub fn update_emoji_picker(&mut self) {
+        let guild = self.viewed_guild().cloned();
+        if let Some(picker) = self.emoji_picker.as_mut() {
+            picker.update(guild.as_ref());
+        }
+    }
+
+    //Everything the switcher can jump to
+    fn switch_targets(&self) -> Vec<SwitchTarget> {
+        let mut targets = Vec::new();
+
+        for guild in &self.guilds.items {
+            targets.push(SwitchTarget {
+                label: guild.name.clone(),
+                guild_id: Some(guild.id.clone()),
+                channel: None,
+            });
+
+            for channel in &guild.channels {
+                targets.push(SwitchTarget {
+                    label: format!("#{} ({})", channel.name, guild.name),
+                    guild_id: Some(guild.id.clone()),
+                    channel: Some(channel.clone()),
+                });
+            }
+        }
+
+        for dm in &self.dms {
+            targets.push(SwitchTarget {
+                label: format!("@{}", dm.name),
+                guild_id: None,
+                channel: Some(dm.clone()),
+            });
+        }
+
+        targets
+    }
+
+    //Jumps to whatever is selected in the switcher and closes it
+    pub fn switch_to_selected(&mut self) {
+        let target = match self.switcher.as_ref().and_then(|v| v.get_target()) {
+            Some(v) => v,
+            None => return,
+        };
+        self.close_switcher();
+        self.switch_to(target);
+    }
+
+    fn switch_to(&mut self, target: SwitchTarget) {
+        match (target.guild_id, target.channel) {
+            (Some(guild_id), channel) => {
+                let index = self.guilds.items.iter().position(|v| v.id == guild_id);
+                self.guilds.state.select(index);
+                self.enter_guild();
+
+                if let Some(channel) = channel {
+                    let index = self.channels.items.iter().position(|v| *v == channel);
+                    self.channels.state.select(index);
+                    self.load_current_channel();
+                }
+            }
+            //DMs get a channel list of their own
+            (None, Some(channel)) => {
+                let index = self.dms.iter().position(|v| *v == channel);
+                self.channels = StatefulList::from(self.dms.clone());
+                self.channels.state.select(index);
+                self.mode = DisplayMode::ChannelMode;
+                self.load_current_channel();
+            }
+            (None, None) => (),
+        }
+    }
+
+    //Nickname in the guild on screen
+    pub fn set_nick(&mut self, nick: &str) {
+        let guild_id = match self.viewed_guild() {
+            Some(v) => v.id.clone(),
+            None => {
+                self.status = Some("Nicknames only work in servers".to_string());
+                return;
+            }
+        };
+
+        self.status = match wrapper::set_nick(&self.conn, &guild_id, nick) {
+            Ok(()) if nick.is_empty() => Some("Nickname reset".to_string()),
+            Ok(()) => Some(format!("Nickname set to {}", nick)),
+            Err(err) => Some(err),
+        };
+    }
+
+    pub fn set_status(&mut self, status: &str) {
+        if let Err(err) = wrapper::set_status(&self.conn, status) {
+            self.status = Some(err);
+            return;
+        }
+        self.presences
+            .insert(self.user.id.clone(), status.to_string());
+        self.status = Some(format!("Status set to {}", status));
+
+        //Being disconnected is worth more than the confirmation
+        let request = gateway_thread::presence_update(status);
+        self.send_to_gateway(request);
+    }
+
+    //A channel of this guild by exact name, otherwise the switcher's best guess
+    pub fn join(&mut self, query: &str) {
+        let name = query.trim_start_matches('#');
+        let exact = self.viewed_guild().and_then(|guild| {
+            let channel = guild
+                .channels
+                .iter()
+                .find(|channel| channel.name.eq_ignore_ascii_case(name))?;
+            Some(SwitchTarget {
+                label: channel.name.clone(),
+                guild_id: Some(guild.id.clone()),
+                channel: Some(channel.clone()),
+            })
+        });
+
+        let target = exact.or_else(|| {
+            let mut switcher = QuickSwitcher::new();
+            switcher.query = name.to_string();
+            switcher.update(self.switch_targets(), &self.recent);
+            switcher.get_target()
+        });
+
+        match target {
+            Some(v) => self.switch_to(v),
+            None => self.status = Some(format!("No channel matches {}", query)),
+        }
+    }
+
+    //Searches the guild on screen, or the DM
+    pub fn search(&mut self, query: &str) {
+        let channel = match self.viewed_channel() {
+            Some(v) => v,
+            None => return,
+        };
+
+        match wrapper::search(&self.conn, &channel.guild_id, &channel.id, query) {
+            Ok(results) if results.is_empty() => {
+                self.status = Some(format!("Nothing found for {}", query))
+            }
+            Ok(results) => self.search = Some(SearchResults::new(query, results)),
+            Err(err) => self.status = Some(err),
+        }
+    }
 
-#[derive(Debug)]
-pub enum DisplayMode {
-    GuildMode,
-    ChannelMode,
-}
+    pub fn close_search(&mut self) {
+        self.search = None;
+    }
 
-pub struct App {
-    pub channels: StatefulList<Channel>,
-    pub guilds: StatefulList<Guild>,
-    pub loaded_channels: HashMap<Channel, StatefulList<Msg>>,
-    pub mode: DisplayMode,
-    pub conn: Connection,
-}
+    //Opens the result's channel and selects the message if it's loaded
+    pub fn jump_to_result(&mut self) {
+        let message = match self.search.as_ref().and_then(|v| v.get_message()) {
+            Some(v) => v,
+            None => return,
+        };
+        self.close_search();
 
-impl App {
-    //build new app
-    pub fn new(guilds: Vec<Guild>, conn: Connection) -> App {
-        App {
-            channels: StatefulList::from(Vec::new()),
-            guilds: StatefulList::from(guilds),
-            loaded_channels: HashMap::new(),
-            mode: DisplayMode::GuildMode,
-            conn,
+        let target = match self.find_channel(&message.channel_id) {
+            Some((guild, channel)) => SwitchTarget {
+                label: channel.name.clone(),
+                guild_id: Some(guild.id.clone()),
+                channel: Some(channel.clone()),
+            },
+            None => match self.dms.iter().find(|dm| dm.id == message.channel_id) {
+                Some(dm) => SwitchTarget {
+                    label: dm.name.clone(),
+                    guild_id: None,
+                    channel: Some(dm.clone()),
+                },
+                None => {
+                    self.status = Some("Can't open that channel".to_string());
+                    return;
+                }
+            },
+        };
+        self.switch_to(target);
+
+        //Older than what got loaded, the channel opening is the best we can do
+        if let Some(messages) = self.viewed_messages() {
+            let index = messages.items.iter().position(|v| v.id == message.id);
+            messages.state.select(index);
         }
     }
 
-    //Very awkward
-    //Use enums and strum crate
-    pub fn react_to_gateway(&mut self, gate_response: &GatewayResponse) {
-        match gate_response.operation.as_str() {
-            "MESSAGE_CREATE" => {
-                let mut channel_found = Vec::new();
-                let gate_channel_id = &gate_response.message.channel_id;
-                for key in self.loaded_channels.keys() {
-                    // println!("current channel id: {}, looking for: {}", &key.id, gate_channel_id);
-                    let channel_id = &key.id;
-                    if channel_id == gate_channel_id {
-                        channel_found.push(key.clone());
-                    }
+    //Leaves the guild on screen, or the group DM
+    //Has to be asked for twice, there's no taking it back
+    pub fn leave(&mut self) {
+        let channel = match self.viewed_channel() {
+            Some(v) => v,
+            None => return,
+        };
+        let guild = self.viewed_guild().cloned();
+        let (id, name) = match &guild {
+            Some(guild) => (guild.id.clone(), guild.name.clone()),
+            None => (channel.id.clone(), channel.name.clone()),
+        };
+
+        if self.leave_armed.as_ref() != Some(&id) {
+            self.leave_armed = Some(id);
+            self.status = Some(format!("Send /leave again to leave {}", name));
+            return;
+        }
+        self.leave_armed = None;
+
+        let result = match guild {
+            Some(_) => wrapper::leave_guild(&self.conn, &id),
+            None => wrapper::close_channel(&self.conn, &id),
+        };
+        if let Err(err) = result {
+            self.status = Some(err);
+            return;
+        }
+
+        self.guilds.items.retain(|guild| guild.id != id);
+        self.dms.retain(|dm| dm.id != id);
+        self.recent
+            .retain(|channel| channel.guild_id != id && channel.id != id);
+        self.guilds.state.select(None);
+        self.mode = DisplayMode::GuildMode;
+        self.status = Some(format!("Left {}", name));
+    }
+
+    //Bot commands are the same across a guild, DMs each have their own
+    fn app_commands_key(channel: &Channel) -> String {
+        match channel.guild_id.as_str() {
+            "@me" => channel.id.clone(),
+            guild_id => guild_id.to_string(),
+        }
+    }
+
+    //Fetches the bot commands for the channel on screen, once it worked
+    //Only Tab and Enter on a /command ask, so a failure just gets tried again next time
+    pub fn load_app_commands(&mut self) {
+        let channel = match self.viewed_channel() {
+            Some(v) => v,
+            None => return,
+        };
+        let key = App::app_commands_key(&channel);
+        if self.app_commands.contains_key(&key) {
+            return;
+        }
+
+        match wrapper::application_commands(&self.conn, &channel.guild_id, &channel.id) {
+            Ok(commands) => {
+                self.app_commands.insert(key, commands);
+            }
+            Err(err) => self.status = Some(err),
+        }
+    }
+
+    pub fn viewed_app_commands(&self) -> &[ApplicationCommand] {
+        self.viewed_channel()
+            .and_then(|channel| self.app_commands.get(&App::app_commands_key(&channel)))
+            .map(|commands| commands.as_slice())
+            .unwrap_or_default()
+    }
+
+    //Sends a bot command, args being everything typed after its name
+    pub fn run_app_command(
+        &mut self,
+        command: &ApplicationCommand,
+        args: &str,
+    ) -> Result<(), String> {
+        let channel = match self.viewed_channel() {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let options = commands::bot_options(&command.options, args)?;
+
+        let mut payload = serde_json::json!({
+            "type": 2,
+            "application_id": command.application_id,
+            "channel_id": channel.id,
+            "session_id": self.session_id,
+            "data": {
+                "version": command.version,
+                "id": command.id,
+                "name": command.name,
+                "type": 1,
+                "options": options,
+                "application_command": command.raw,
+                "attachments": [],
+            },
+            "nonce": nonce(),
+        });
+        if channel.guild_id != "@me" {
+            payload["guild_id"] = Value::from(channel.guild_id);
+        }
+
+        wrapper::send_interaction(&self.conn, &payload)
+    }
+
+    //Moves onto the buttons and menus of the selected message
+    pub fn focus_components(&mut self) {
+        let message = match self.selected_message() {
+            Some(v) => v,
+            None => {
+                self.status = Some("Select a message first (j/k)".to_string());
+                return;
+            }
+        };
+        match message.components.is_empty() {
+            true => self.status = Some("That message has no buttons or menus".to_string()),
+            false => self.component_focus = Some(ComponentFocus::new(&message)),
+        }
+    }
+
+    pub fn close_components(&mut self) {
+        self.component_focus = None;
+    }
+
+    pub fn step_component(&mut self, forward: bool) {
+        let count = match self.focused_message() {
+            Some(v) => components::flatten(&v).len(),
+            None => return,
+        };
+        if let Some(focus) = self.component_focus.as_mut() {
+            focus.step(count, forward);
+        }
+    }
+
+    //The message with focused components, as it is now
+    fn focused_message(&self) -> Option<Msg> {
+        let focus = self.component_focus.as_ref()?;
+        self.find_message(&focus.channel_id, &focus.message_id)
+            .cloned()
+    }
+
+    //Clicks the focused button, or opens the focused menu
+    pub fn press_component(&mut self) {
+        let message = match self.focused_message() {
+            Some(v) => v,
+            None => return self.close_components(),
+        };
+        let index = self.component_focus.as_ref().map_or(0, |v| v.index);
+        let component = match components::flatten(&message).get(index) {
+            Some(v) => (*v).clone(),
+            None => return,
+        };
+        if component.disabled() {
+            self.status = Some("That one is disabled".to_string());
+            return;
+        }
+
+        match component {
+            Component::Button(button) => {
+                if let Some(url) = &button.url {
+                    opener::open_with(self.config.links.browser.as_deref(), url);
+                    self.status = Some(format!("Opened {}", url));
+                    return;
                 }
+                let data = serde_json::json!({
+                    "component_type": 2,
+                    "custom_id": button.custom_id,
+                });
+                self.component_interaction(&message, data);
+            }
+            Component::Select(menu) if menu.kind == 3 => {
+                self.select_picker = Some(SelectPicker::new(message, menu));
+            }
+            //Those list users/roles/channels discord fills in, not options
+            Component::Select(_) => {
+                self.status = Some("That kind of menu isn't supported".to_string());
+            }
+        }
+    }
 
-                for key in channel_found {
-                    let mut old_messages = self.loaded_channels[&key].clone();
-                    old_messages.items.push(gate_response.message.clone());
-                    //updates the messages with the new one
-                    self.loaded_channels.insert(key, old_messages);
-                    // dbg!(&self.loaded_channels);
+    pub fn close_select_picker(&mut self) {
+        self.select_picker = None;
+    }
+
+    //Sends what's picked in the open menu
+    pub fn submit_select(&mut self) {
+        let picker = match self.select_picker.as_ref() {
+            Some(v) => v,
+            None => return,
+        };
+        let values = match picker.values() {
+            Ok(v) => v,
+            Err(err) => {
+                self.status = Some(err);
+                return;
+            }
+        };
+
+        let data = serde_json::json!({
+            "component_type": picker.menu.kind,
+            "custom_id": picker.menu.custom_id,
+            "type": picker.menu.kind,
+            "values": values,
+        });
+        let message = picker.message.clone();
+        self.select_picker = None;
+        self.component_interaction(&message, data);
+    }
+
+    //Tells the bot, whatever it changes comes back as MESSAGE_UPDATE
+    fn component_interaction(&mut self, message: &Msg, data: Value) {
+        let application_id = match &message.application_id {
+            Some(v) => v.clone(),
+            None => {
+                self.status = Some("Can't tell which bot that belongs to".to_string());
+                return;
+            }
+        };
+
+        let mut payload = serde_json::json!({
+            "type": 3,
+            "application_id": application_id,
+            "channel_id": message.channel_id,
+            "message_id": message.id,
+            "message_flags": message.flags,
+            "session_id": self.session_id,
+            "data": data,
+            "nonce": nonce(),
+        });
+        if let Some((guild, _)) = self.find_channel(&message.channel_id) {
+            payload["guild_id"] = Value::from(guild.id.clone());
+        }
+
+        if let Err(err) = wrapper::send_interaction(&self.conn, &payload) {
+            self.status = Some(err);
+        }
+    }
+
+    fn guild_mut(&mut self, guild_id: &str) -> Option<&mut Guild> {
+        self.guilds
+            .items
+            .iter_mut()
+            .find(|guild| guild.id == guild_id)
+    }
+
+    //New or changed thread, archived ones leave the sidebar
+    fn update_thread(&mut self, mut thread: Channel) {
+        let old = self
+            .find_channel(&thread.id)
+            .and_then(|(_, channel)| channel.thread.clone());
+        if let (Some(old), Some(info)) = (old, thread.thread.as_mut()) {
+            //Updates don't say whether we're in it
+            info.joined |= old.joined;
+        }
+
+        self.last_messages
+            .entry(thread.id.clone())
+            .or_insert(thread.last_message_id.clone());
+        self.update_starter(&thread);
+
+        let archived = thread.thread.as_ref().is_some_and(|info| info.archived);
+        let guild_id = thread.guild_id.clone();
+        if let Some(guild) = self.guild_mut(&guild_id) {
+            match archived {
+                true => guild.remove_channel(&thread.id),
+                false => {
+                    guild.insert_thread(thread);
                 }
             }
-            "READY" => {
-                dbg!(&gate_response.guilds);
+        }
+        self.refresh_channels(&guild_id);
+    }
+
+    //Replaces the active threads of the channels it covers
+    fn sync_threads(&mut self, sync: &ThreadListSync) {
+        for thread in &sync.threads {
+            self.last_messages
+                .entry(thread.id.clone())
+                .or_insert(thread.last_message_id.clone());
+        }
+
+        let guild = match self.guild_mut(&sync.guild_id) {
+            Some(v) => v,
+            None => return,
+        };
+        guild.channels.retain(|channel| {
+            let covered = match (&sync.channel_ids, &channel.parent_id) {
+                (None, _) => true,
+                (Some(ids), Some(parent)) => ids.contains(parent),
+                (Some(_), None) => false,
+            };
+            !(channel.is_thread() && covered)
+        });
+        for thread in &sync.threads {
+            guild.insert_thread(thread.clone());
+        }
+        self.refresh_channels(&sync.guild_id);
+    }
+
+    fn set_thread_joined(&mut self, thread_id: &str, joined: bool) {
+        let guild_id = match self.find_channel(thread_id) {
+            Some((guild, _)) => guild.id.clone(),
+            None => return,
+        };
+        if let Some(guild) = self.guild_mut(&guild_id) {
+            for channel in guild.channels.iter_mut() {
+                if let (true, Some(info)) = (channel.id == thread_id, channel.thread.as_mut()) {
+                    info.joined = joined;
+                }
             }
-            _ => (),
         }
+        self.refresh_channels(&guild_id);
     }
 
-    pub fn enter_guild(&mut self) {
-        let current_guild = self.get_guild();
-        let channels = current_guild.channels;
+    //A message in a thread, one more reply on it and its starter message
+    fn count_thread_reply(&mut self, channel_id: &str) {
+        let mut thread = match self.find_channel(channel_id) {
+            Some((_, channel)) if channel.is_thread() => channel.clone(),
+            _ => return,
+        };
+        if let Some(info) = thread.thread.as_mut() {
+            info.message_count += 1;
+        }
+        self.update_thread(thread);
+    }
+
+    //Threads started from a message share its id
+    fn update_starter(&mut self, thread: &Channel) {
+        for (channel, messages) in self.loaded_channels.iter_mut() {
+            if Some(&channel.id) != thread.parent_id.as_ref() {
+                continue;
+            }
+            for message in messages.items.iter_mut() {
+                if message.id == thread.id {
+                    message.thread = Some(thread.clone());
+                }
+            }
+        }
+    }
+
+    //Rebuilds the sidebar after a guild's channels changed, if it's the one open
+    fn refresh_channels(&mut self, guild_id: &str) {
+        let viewed = match self.viewed_channel() {
+            Some(v) if v.guild_id == guild_id => v,
+            _ => return,
+        };
+        //New posts show up right away in the forum on screen
+        if viewed.is_forum() {
+            self.load_forum(&viewed);
+        }
+        let channels = match self.guilds.items.iter().find(|guild| guild.id == guild_id) {
+            Some(guild) => guild.channels.clone(),
+            None => return,
+        };
+        //Leave it be if the open channel is gone, it'd lose the selection
+        let index = match channels.iter().position(|channel| *channel == viewed) {
+            Some(v) => v,
+            None => return,
+        };
 
         self.channels = StatefulList::from(channels);
-        self.mode = DisplayMode::ChannelMode;
+        self.channels.state.select(Some(index));
     }
 
-    pub fn leave_guild(&mut self) {
-        self.mode = DisplayMode::GuildMode;
+    //The forum on screen, if it is one
+    pub fn viewed_forum(&self) -> Option<Channel> {
+        self.viewed_channel().filter(|channel| channel.is_forum())
+    }
+
+    //Lists the forum's posts, archived ones only get fetched the first time
+    fn load_forum(&mut self, forum: &Channel) {
+        if !self.archived_posts.contains_key(&forum.id) {
+            let archived = match wrapper::archived_threads(&self.conn, &forum.id) {
+                Ok(v) => v,
+                Err(err) => {
+                    self.status = Some(err);
+                    Vec::new()
+                }
+            };
+            self.archived_posts.insert(forum.id.clone(), archived);
+        }
+
+        let active: Vec<Channel> = match self.guilds.items.iter().find(|v| v.id == forum.guild_id) {
+            Some(guild) => guild
+                .posts
+                .iter()
+                .filter(|post| post.parent_id.as_ref() == Some(&forum.id))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        let posts = forum::merge_posts(active, &self.archived_posts[&forum.id]);
+
+        //Keep the cursor on the same post
+        let selected = self
+            .forum_posts
+            .state
+            .selected()
+            .and_then(|i| self.forum_posts.items.get(i))
+            .and_then(|post| posts.iter().position(|v| v == post));
+        let empty = posts.is_empty();
+        self.forum_posts = StatefulList::from(posts);
+        match (selected, empty) {
+            (Some(i), _) => self.forum_posts.state.select(Some(i)),
+            (None, false) => self.forum_posts.state.select(Some(0)),
+            (None, true) => (),
+        }
+    }
+
+    //Opens the selected post like a thread, nested under the forum in the sidebar
+    fn open_post(&mut self) {
+        let post = match self
+            .forum_posts
+            .state
+            .selected()
+            .and_then(|i| self.forum_posts.items.get(i))
+        {
+            Some(v) => v.clone(),
+            None => return,
+        };
+        self.show_thread(post);
+    }
+
+    //Opens a thread, putting it in the sidebar first if it isn't there
+    fn show_thread(&mut self, thread: Channel) {
+        let guild_id = thread.guild_id.clone();
+        if let Some(guild) = self.guild_mut(&guild_id) {
+            let shown = guild.channels.contains(&thread);
+            if !shown && !guild.show_thread(thread.clone()) {
+                guild.channels.push(thread.clone());
+            }
+        }
+        self.switch_to(SwitchTarget {
+            label: thread.name.clone(),
+            guild_id: Some(guild_id),
+            channel: Some(thread),
+        });
+    }
+
+    //Starts a post in the forum on screen and opens it
+    pub fn create_post(&mut self, args: &str) {
+        let forum = match self.viewed_forum() {
+            Some(v) => v,
+            None => {
+                self.status = Some("Open a forum first".to_string());
+                return;
+            }
+        };
+        let post = match forum::parse_post(args, &forum.available_tags) {
+            Ok(v) => v,
+            Err(err) => {
+                self.status = Some(err);
+                return;
+            }
+        };
+
+        let result =
+            wrapper::create_post(&self.conn, &forum.id, &post.title, &post.tags, &post.body);
+        match result {
+            Ok(mut thread) => {
+                thread.guild_id = forum.guild_id.clone();
+                if let Some(guild) = self.guild_mut(&forum.guild_id) {
+                    guild.insert_thread(thread.clone());
+                }
+                self.show_thread(thread);
+                self.status = Some(format!("Posted {}", post.title));
+            }
+            Err(err) => self.status = Some(err),
+        }
+    }
+
+    //Opens the thread started from the selected message, or the selected forum post
+    pub fn open_thread(&mut self) {
+        if self.viewed_forum().is_some() {
+            return self.open_post();
+        }
+        let message = match self.selected_message() {
+            Some(v) => v,
+            None => {
+                self.status = Some("Select a message first (j/k)".to_string());
+                return;
+            }
+        };
+        let mut thread = match message.thread {
+            Some(v) => v,
+            None => {
+                self.status = Some("No thread on that message".to_string());
+                return;
+            }
+        };
+        let guild_id = match self.viewed_guild() {
+            Some(v) => v.id.clone(),
+            None => return,
+        };
+        thread.guild_id = guild_id;
+
+        //Archived threads aren't in the sidebar, they go at the bottom until the next sync
+        self.show_thread(thread);
+    }
+
+    //Joins or leaves the thread on screen
+    pub fn join_thread(&mut self, join: bool) {
+        let thread = match self.viewed_channel() {
+            Some(v) if v.is_thread() => v,
+            _ => {
+                self.status = Some("Open a thread first".to_string());
+                return;
+            }
+        };
+
+        let result = match join {
+            true => wrapper::join_thread(&self.conn, &thread.id),
+            false => wrapper::leave_thread(&self.conn, &thread.id),
+        };
+        match result {
+            Ok(()) => {
+                self.set_thread_joined(&thread.id, join);
+                self.status = match join {
+                    true => Some(format!("Joined {}", thread.name)),
+                    false => Some(format!("Left {}", thread.name)),
+                };
+            }
+            Err(err) => self.status = Some(err),
+        }
+    }
+
+    //Publishes the selected announcement to the channels following this one
+    pub fn publish(&mut self) {
+        let channel = match self.viewed_channel() {
+            Some(v) if v.is_announcement() => v,
+            _ => {
+                self.status = Some("Only announcement channels can publish".to_string());
+                return;
+            }
+        };
+        let message = match self.selected_message() {
+            Some(v) => v,
+            None => {
+                self.status = Some("Select a message to publish first".to_string());
+                return;
+            }
+        };
+        if message.is_published() {
+            self.status = Some("Already published".to_string());
+            return;
+        }
+        //Our own messages only need us to be able to post there
+        let needed = match message.user.id == self.user.id {
+            true => SEND_MESSAGES,
+            false => MANAGE_MESSAGES,
+        };
+        if !self.can(&channel, needed) {
+            self.status = Some("You can't publish that message here".to_string());
+            return;
+        }
+
+        self.status = match wrapper::crosspost(&self.conn, &channel.id, &message.id) {
+            Ok(()) => Some("Published".to_string()),
+            Err(err) => Some(err),
+        };
+    }
+
+    //Whether we have permission for something in a guild channel, DMs allow everything
+    pub fn can(&self, channel: &Channel, permission: u64) -> bool {
+        match self.guilds.items.iter().find(|v| v.id == channel.guild_id) {
+            Some(guild) => guild.permissions(channel, &self.user.id) & permission == permission,
+            None => true,
+        }
+    }
+
+    //Text channels in any of our guilds, announcements can only be followed into those
+    //Following makes a webhook there, so we need to be allowed to
+    pub fn follow_targets(&self) -> Vec<SwitchTarget> {
+        let mut targets = Vec::new();
+        for guild in &self.guilds.items {
+            for channel in &guild.channels {
+                if channel.channel_type != "0"
+                    || guild.permissions(channel, &self.user.id) & MANAGE_WEBHOOKS == 0
+                {
+                    continue;
+                }
+                targets.push(SwitchTarget {
+                    label: format!("#{} ({})", channel.name, guild.name),
+                    guild_id: Some(guild.id.clone()),
+                    channel: Some(channel.clone()),
+                });
+            }
+        }
+        targets
+    }
+
+    //Follows the announcement channel on screen into a channel matching query
+    pub fn follow(&mut self, query: &str) {
+        let channel = match self.viewed_channel() {
+            Some(v) if v.is_announcement() => v,
+            _ => {
+                self.status = Some("Open an announcement channel first".to_string());
+                return;
+            }
+        };
+
+        let targets = self.follow_targets();
+        let exact = targets.iter().find(|target| target.label == query).cloned();
+        let target = exact.or_else(|| {
+            let mut switcher = QuickSwitcher::new();
+            switcher.query = query.trim_start_matches('#').to_string();
+            switcher.update(targets, &self.recent);
+            switcher.get_target()
+        });
+        let target = match target {
+            Some(v) => v,
+            None => {
+                self.status = Some(format!(
+                    "No channel you can manage webhooks in matches {}",
+                    query
+                ));
+                return;
+            }
+        };
+
+        let target_id = target.channel.map(|v| v.id).unwrap_or_default();
+        self.status = match wrapper::follow_channel(&self.conn, &channel.id, &target_id) {
+            Ok(()) => Some(format!("Following #{} in {}", channel.name, target.label)),
+            Err(err) => Some(err),
+        };
+    }
+
+    //Opens the friends list or goes back to guilds
+    pub fn toggle_friends(&mut self) {
+        match self.mode {
+            DisplayMode::FriendsMode => self.mode = DisplayMode::GuildMode,
+            _ => {
+                self.refresh_friends();
+                self.mode = DisplayMode::FriendsMode;
+            }
+        }
+    }
+
+    //Refetches relationships, keeps the cursor where it was
+    pub fn refresh_friends(&mut self) {
+        let mut friends = wrapper::friends(&self.conn);
+        for friend in friends.iter_mut() {
+            if let Some(status) = self.presences.get(&friend.user.id) {
+                friend.status = status.clone();
+            }
+        }
+        //Friends first, then requests, blocked at the bottom
+        friends.sort_by_key(|friend| match friend.kind {
+            RelationshipKind::Friend => 0,
+            RelationshipKind::Incoming => 1,
+            RelationshipKind::Outgoing => 2,
+            RelationshipKind::Blocked => 3,
+            RelationshipKind::Other => 4,
+        });
+
+        let selected = self.friends.state.selected();
+        self.friends = StatefulList::from(friends);
+        if !self.friends.items.is_empty() {
+            let index = selected.unwrap_or_default();
+            self.friends
+                .state
+                .select(Some(index.min(self.friends.items.len() - 1)));
+        }
+    }
+
+    pub fn get_friend(&mut self) -> Option<Relationship> {
+        let index = self.friends.state.selected()?;
+        self.friends.items.get(index).cloned()
+    }
+
+    pub fn accept_friend(&mut self) {
+        if let Some(friend) = self.get_friend() {
+            if friend.kind == RelationshipKind::Incoming {
+                match wrapper::accept_friend(&self.conn, &friend.user) {
+                    Ok(()) => self.refresh_friends(),
+                    Err(err) => self.status = Some(err),
+                }
+            }
+        }
+    }
+
+    //Unfriends, cancels or declines a request, or unblocks
+    //Has to be pressed twice like /leave, there's no taking it back
+    pub fn remove_friend(&mut self) {
+        let friend = match self.get_friend() {
+            Some(v) => v,
+            None => return,
+        };
+
+        if self.remove_armed.as_ref() != Some(&friend.user.id) {
+            let action = match friend.kind {
+                RelationshipKind::Friend => "unfriend",
+                RelationshipKind::Blocked => "unblock",
+                RelationshipKind::Incoming => "decline",
+                RelationshipKind::Outgoing | RelationshipKind::Other => "cancel the request to",
+            };
+            self.remove_armed = Some(friend.user.id.clone());
+            self.status = Some(format!("Press d again to {} {}", action, friend.user.name));
+            return;
+        }
+        self.remove_armed = None;
+
+        match wrapper::remove_relationship(&self.conn, &friend.user) {
+            Ok(()) => self.refresh_friends(),
+            Err(err) => self.status = Some(err),
+        }
+    }
+
+    //Jumps into the DM with the selected friend
+    pub fn open_dm(&mut self) {
+        let friend = match self.get_friend() {
+            Some(v) => v,
+            None => return,
+        };
+
+        let dm = match wrapper::open_dm(&self.conn, &friend.user) {
+            Ok(v) => v,
+            Err(err) => {
+                self.status = Some(err);
+                return;
+            }
+        };
+        self.channels = StatefulList::from(vec![dm]);
+        self.channels.state.select(Some(0));
+        self.mode = DisplayMode::ChannelMode;
+        self.load_current_channel();
     }
 
     //get current selected channel object
@@ -90,13 +2125,14 @@ impl App {
         match self.mode {
             DisplayMode::GuildMode => self.get_guild().name,
             DisplayMode::ChannelMode => self.get_channel().name,
+            DisplayMode::FriendsMode => "Friends".to_string(),
         }
     }
 
     //CLONES EVERYTIME, PROBABLY SLOW
     pub fn get_messages(&mut self) -> Option<StatefulList<Msg>> {
         match self.mode {
-            DisplayMode::GuildMode => return None,
+            DisplayMode::GuildMode | DisplayMode::FriendsMode => return None,
             DisplayMode::ChannelMode => {}
         }
 
@@ -115,28 +2151,16 @@ impl App {
                 self.guilds.next();
                 return;
             }
+            DisplayMode::FriendsMode => {
+                self.friends.next();
+                return;
+            }
             DisplayMode::ChannelMode => {
                 self.channels.next();
             }
         }
 
-        let current_channel = self.get_channel();
-        //Check whether the channel has already been loaded
-        //Don't wanna spam discord
-        if !(self.loaded_channels.contains_key(&current_channel)) {
-            let messages = wrapper::messages(&self.conn, &current_channel);
-
-            match messages {
-                Ok(v) => {
-                    self.loaded_channels
-                        .insert(current_channel, StatefulList::from(v));
-                }
-                Err(_) => {
-                    self.loaded_channels
-                        .insert(current_channel, StatefulList::from(vec![Msg::new()]));
-                }
-            }
-        }
+        self.load_current_channel();
     }
 
     //Moves cursor up
@@ -146,12 +2170,25 @@ impl App {
                 self.guilds.previous();
                 return;
             }
+            DisplayMode::FriendsMode => {
+                self.friends.previous();
+                return;
+            }
             DisplayMode::ChannelMode => {
                 self.channels.previous();
             }
         }
 
+        self.load_current_channel();
+    }
+
+    fn load_current_channel(&mut self) {
         let current_channel = self.get_channel();
+        //Forums have posts instead of messages
+        if current_channel.is_forum() {
+            self.load_forum(&current_channel);
+            return;
+        }
         //Check whether the channel has already been loaded
         //Don't wanna spam discord
         if !(self.loaded_channels.contains_key(&current_channel)) {
@@ -160,17 +2197,47 @@ impl App {
             match messages {
                 Ok(v) => {
                     self.loaded_channels
-                        .insert(current_channel, StatefulList::from(v));
+                        .insert(current_channel.clone(), StatefulList::from(v));
+                    self.request_authors(&current_channel);
                 }
                 Err(_) => {
-                    self.loaded_channels
-                        .insert(current_channel, StatefulList::from(vec![Msg::new()]));
+                    self.loaded_channels.insert(
+                        current_channel.clone(),
+                        StatefulList::from(vec![Msg::new()]),
+                    );
                 }
             }
         }
+
+        //DMs opened from the friends list aren't in READY
+        if !self.last_messages.contains_key(&current_channel.id) {
+            self.last_messages.insert(
+                current_channel.id.clone(),
+                current_channel.last_message_id.clone(),
+            );
+        }
+        self.mark_read(&current_channel);
+
+        self.recent.retain(|channel| *channel != current_channel);
+        self.recent.insert(0, current_channel.clone());
+        self.recent.truncate(50);
+
+        if self.show_members {
+            self.subscribe_members();
+        }
     }
 
     pub fn unselect(&mut self) {
         self.channels.state.select(None);
     }
 }
+
+//A made up snowflake for right now, discord uses it to match up the reply
+fn nonce() -> String {
+    const DISCORD_EPOCH: u128 = 1_420_070_400_000;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    ((now.saturating_sub(DISCORD_EPOCH)) << 22).to_string()
+}
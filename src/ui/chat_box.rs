@@ -1,4 +1,15 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
 use crate::ui::channels::{App, DisplayMode};
+use crate::ui::commands::{self, SlashCommand};
+use crate::ui::completion::{self, Candidate, Completion};
+use crate::ui::emoji;
+use crate::ui::upload;
+use crate::ui::editor;
+use crate::config::Config;
 use crate::api::wrapper;
 
 #[derive(PartialEq, Debug)]
@@ -13,6 +24,26 @@ pub struct ChatBox {
     pub input: String,
     // Current input mode
     pub input_mode: InputMode,
+    // Byte index into input, always on a grapheme boundary
+    pub cursor: usize,
+    // How many rows the input is scrolled down, once it's taller than the box
+    pub scroll: usize,
+    // Messages sent this session, oldest first
+    history: Vec<String>,
+    // Where we are when going through history, None when not
+    history_index: Option<usize>,
+    // What was typed before going into history
+    history_draft: String,
+    // Unsent text of every other channel, by channel id
+    drafts: HashMap<String, String>,
+    // The channel input belongs to
+    draft_channel: Option<String>,
+    // Set while Tab is cycling through completions
+    pub completion: Option<Completion>,
+    // Completed @name/#channel/:emoji: text and the markup it's sent as
+    markups: HashMap<String, String>,
+    // Files that go out with the next message
+    pub attachments: Vec<PathBuf>,
 }
 
 impl ChatBox {
@@ -20,6 +51,16 @@ impl ChatBox {
         ChatBox {
             input: String::new(),
             input_mode: InputMode::Normal,
+            cursor: 0,
+            scroll: 0,
+            history: Vec::new(),
+            history_index: None,
+            history_draft: String::new(),
+            drafts: load_drafts(),
+            draft_channel: None,
+            completion: None,
+            markups: HashMap::new(),
+            attachments: Vec::new(),
         }
     }
 
@@ -35,13 +76,624 @@ impl ChatBox {
     //Sends message from the chat box, clears it
     pub fn send_message(&mut self, app: &mut App) {
         match app.mode {
-            DisplayMode::GuildMode => {self.input_mode = InputMode::Normal},
+            DisplayMode::GuildMode | DisplayMode::FriendsMode => {self.input_mode = InputMode::Normal},
             DisplayMode::ChannelMode => {
                 //Here so messages dissappear instantly
                 let input_copy = self.input.clone();
-                self.input.clear();
-                wrapper::send_message(app, &input_copy);
+                self.set_input(String::new());
+                self.history_index = None;
+                if !input_copy.is_empty() {
+                    self.history.push(input_copy.clone());
+                }
+
+                if input_copy.starts_with('/') {
+                    app.load_app_commands();
+                }
+                match commands::parse(&input_copy, app.viewed_app_commands()) {
+                    Some(Ok(command)) => self.run_command(app, command, &input_copy),
+                    //Nothing unknown gets posted, the text stays to be fixed
+                    Some(Err(err)) => {
+                        app.status = Some(err);
+                        self.set_input(input_copy);
+                    }
+                    None => {
+                        //Escaped slash
+                        let text = input_copy.strip_prefix('/').filter(|v| v.starts_with('/'));
+                        self.send_text(app, text.unwrap_or(&input_copy), &input_copy);
+                    }
+                }
+                //Completions only count for the message they were made in
+                //unless it came back to be fixed
+                if self.input.is_empty() {
+                    self.markups.clear();
+                }
+                //So a sent message doesn't come back as a draft after a restart
+                self.save_drafts();
             },
         }
     }
-}
\ No newline at end of file
+
+    //input is what was typed, so it can be put back if sending fails
+    fn send_text(&mut self, app: &mut App, text: &str, input: &str) {
+        //Forums only take posts
+        if app.viewed_forum().is_some() {
+            app.status = Some("Start a post with /post [+tag] <title> | <body>".to_string());
+            self.set_input(input.to_string());
+            return;
+        }
+        let content = completion::to_markup(text, &self.markups);
+        let content = emoji::from_shortcodes(&content, app.viewed_guild());
+        if self.attachments.is_empty() {
+            wrapper::send_message(app, &content);
+        } else {
+            let files = std::mem::take(&mut self.attachments);
+            //Put everything back so nothing's lost
+            if !app.send_attachments(content, files.clone()) {
+                self.attachments = files;
+                self.set_input(input.to_string());
+            }
+        }
+    }
+
+    fn run_command(&mut self, app: &mut App, command: SlashCommand, input: &str) {
+        match command {
+            SlashCommand::Send(text) => self.send_text(app, &text, input),
+            SlashCommand::Nick(nick) => app.set_nick(&nick),
+            SlashCommand::Status(status) => app.set_status(&status),
+            SlashCommand::Join(channel) => app.join(&channel),
+            SlashCommand::Upload(args) => self.upload_command(app, &args),
+            SlashCommand::Search(query) => app.search(&query),
+            SlashCommand::Leave => app.leave(),
+            SlashCommand::Thread(join) => app.join_thread(join),
+            SlashCommand::Post(args) => app.create_post(&args),
+            SlashCommand::Publish => app.publish(),
+            SlashCommand::Follow(target) => app.follow(&target),
+            SlashCommand::Help => app.show_help = true,
+            //Mentions picked with Tab become ids here
+            SlashCommand::Bot(command, args) => {
+                let args = completion::to_markup(&args, &self.markups);
+                if let Err(err) = app.run_app_command(&command, &args) {
+                    app.status = Some(err);
+                    self.set_input(input.to_string());
+                }
+            }
+        }
+    }
+
+    fn upload_command(&mut self, app: &mut App, args: &str) {
+        let paths = upload::parse_paths(args);
+        if paths.is_empty() {
+            app.open_file_picker();
+            return;
+        }
+
+        match paths.iter().find(|path| !path.is_file()) {
+            Some(path) => app.status = Some(format!("{} isn't a file", path.display())),
+            None => self.attach(paths),
+        }
+    }
+
+    pub fn attach(&mut self, files: Vec<PathBuf>) {
+        for file in files {
+            if !self.attachments.contains(&file) {
+                self.attachments.push(file);
+            }
+        }
+    }
+
+    //Swaps drafts when the channel on screen changes, call after anything that can switch
+    //Nothing changes while no channel is open, the text stays for wherever we go next
+    pub fn follow_channel(&mut self, app: &App) {
+        let channel_id = match app.viewed_channel() {
+            Some(v) => v.id,
+            None => return,
+        };
+        if self.draft_channel.as_ref() == Some(&channel_id) {
+            return;
+        }
+
+        match self.draft_channel.take() {
+            Some(old_id) => {
+                let input = std::mem::take(&mut self.input);
+                self.store_draft(old_id, input);
+                let draft = self.drafts.remove(&channel_id).unwrap_or_default();
+                self.set_input(draft);
+            }
+            //First channel opened, whatever got typed before it belongs here
+            //A draft saved last time goes first so neither gets lost
+            None => {
+                if let Some(draft) = self.drafts.remove(&channel_id) {
+                    let input = match self.input.is_empty() {
+                        true => draft,
+                        false => format!("{}\n{}", draft, self.input),
+                    };
+                    self.set_input(input);
+                }
+            }
+        }
+        //Files and completed names were meant for the old channel
+        self.attachments.clear();
+        self.markups.clear();
+        self.history_index = None;
+        self.draft_channel = Some(channel_id);
+        self.save_drafts();
+    }
+
+    //Writes every draft, including the one being typed, to disk
+    pub fn save_drafts(&self) {
+        let path = match drafts_path() {
+            Some(v) => v,
+            None => return,
+        };
+
+        let mut drafts = self.drafts.clone();
+        if let Some(channel_id) = &self.draft_channel {
+            if !self.input.is_empty() {
+                drafts.insert(channel_id.clone(), self.input.clone());
+            }
+        }
+
+        //Not worth crashing over
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        if let Ok(text) = serde_json::to_string(&drafts) {
+            let _ = std::fs::write(path, text);
+        }
+    }
+
+    fn store_draft(&mut self, channel_id: String, input: String) {
+        match input.is_empty() {
+            true => self.drafts.remove(&channel_id),
+            false => self.drafts.insert(channel_id, input),
+        };
+    }
+
+    //Tab completes the word before the cursor if it starts with @, # or :
+    //Pressing it again goes to the next candidate, backwards when not forward
+    pub fn complete(&mut self, app: &mut App, forward: bool) {
+        let completion = match self.completion.as_mut() {
+            Some(v) => {
+                let count = v.candidates.len();
+                v.index = match forward {
+                    true => (v.index + 1) % count,
+                    false => (v.index + count - 1) % count,
+                };
+                v
+            }
+            None => {
+                if self.input.starts_with('/') {
+                    app.load_app_commands();
+                }
+                let (start, candidates) = match commands::complete(app, &self.input[..self.cursor])
+                {
+                    Some(v) => v,
+                    None => {
+                        let start = self.input[..self.cursor]
+                            .rfind(char::is_whitespace)
+                            .map_or(0, |i| i + self.input[i..].chars().next().unwrap().len_utf8());
+                        (start, completion::candidates(app, &self.input[start..self.cursor]))
+                    }
+                };
+                if candidates.is_empty() {
+                    return;
+                }
+
+                self.completion.insert(Completion {
+                    start,
+                    candidates,
+                    index: 0,
+                })
+            }
+        };
+
+        let candidate = completion.get_candidate().clone();
+        let start = completion.start;
+        //Folders and option names keep going, everything else gets a space after
+        let keep_going = candidate.text.ends_with('/')
+            || (candidate.text.ends_with(':') && !candidate.text.starts_with(':'));
+        let text = match keep_going {
+            true => candidate.text.clone(),
+            false => format!("{} ", candidate.text),
+        };
+        self.input.replace_range(start..self.cursor, &text);
+        self.cursor = start + text.len();
+        if let Some(markup) = candidate.markup {
+            self.markups.insert(candidate.text, markup);
+        }
+    }
+
+    //From the emoji picker, custom ones are sent as their markup
+    pub fn insert_emoji(&mut self, candidate: Candidate) {
+        self.insert_str(&candidate.text);
+        if let Some(markup) = candidate.markup {
+            self.markups.insert(candidate.text, markup);
+        }
+    }
+
+    //Replaces everything and puts the cursor at the end
+    pub fn set_input(&mut self, input: String) {
+        self.cursor = input.len();
+        self.input = input;
+    }
+
+    //Whatever is saved in the editor replaces the draft
+    pub fn open_editor(&mut self) {
+        if let Some(text) = editor::compose(&self.input) {
+            self.history_index = None;
+            self.set_input(text);
+        }
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        self.input.insert(self.cursor, c);
+        self.cursor += c.len_utf8();
+    }
+
+    //Pastes come in whole thanks to bracketed paste
+    pub fn insert_str(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.input.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    pub fn backspace(&mut self) {
+        let start = self.prev_grapheme();
+        self.input.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    pub fn delete(&mut self) {
+        let end = self.next_grapheme();
+        self.input.replace_range(self.cursor..end, "");
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.prev_grapheme();
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = self.next_grapheme();
+    }
+
+    pub fn word_left(&mut self) {
+        self.cursor = self.prev_word();
+    }
+
+    pub fn word_right(&mut self) {
+        self.cursor = self.next_word();
+    }
+
+    //Start of the current line
+    pub fn home(&mut self) {
+        self.cursor = self.line_start();
+    }
+
+    //End of the current line
+    pub fn end(&mut self) {
+        self.cursor = match self.input[self.cursor..].find('\n') {
+            Some(i) => self.cursor + i,
+            None => self.input.len(),
+        };
+    }
+
+    //Up a line, or into history when already on the first one
+    pub fn up(&mut self) {
+        let start = self.line_start();
+        if start == 0 {
+            self.history_previous();
+            return;
+        }
+
+        let column = self.input[start..self.cursor].graphemes(true).count();
+        let previous_start = self.input[..start - 1].rfind('\n').map_or(0, |i| i + 1);
+        self.cursor = self.column_in_line(previous_start, column);
+    }
+
+    //Down a line, or out of history when already on the last one
+    pub fn down(&mut self) {
+        let next_start = match self.input[self.cursor..].find('\n') {
+            Some(i) => self.cursor + i + 1,
+            None => {
+                self.history_next();
+                return;
+            }
+        };
+
+        let column = self.input[self.line_start()..self.cursor].graphemes(true).count();
+        self.cursor = self.column_in_line(next_start, column);
+    }
+
+    //Ctrl-W
+    pub fn delete_word(&mut self) {
+        let start = self.prev_word();
+        self.input.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    //Ctrl-U, only clears the current line
+    pub fn delete_to_start(&mut self) {
+        let start = self.line_start();
+        self.input.replace_range(start..self.cursor, "");
+        self.cursor = start;
+    }
+
+    //Up, goes to older messages
+    pub fn history_previous(&mut self) {
+        let index = match self.history_index {
+            Some(0) => return,
+            Some(i) => i - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.history_draft = self.input.clone();
+                self.history.len() - 1
+            }
+        };
+
+        self.history_index = Some(index);
+        self.set_input(self.history[index].clone());
+    }
+
+    //Down, back towards what was being typed
+    pub fn history_next(&mut self) {
+        let index = match self.history_index {
+            Some(i) => i + 1,
+            None => return,
+        };
+
+        if index < self.history.len() {
+            self.history_index = Some(index);
+            self.set_input(self.history[index].clone());
+        } else {
+            self.history_index = None;
+            let draft = std::mem::take(&mut self.history_draft);
+            self.set_input(draft);
+        }
+    }
+
+    //Breaks the input into rows no wider than width, plus where the cursor is in them
+    pub fn wrap(&self, width: usize) -> (Vec<String>, (usize, usize)) {
+        let width = width.max(1);
+        let mut rows = vec![String::new()];
+        let mut row_width = 0;
+        let mut cursor = None;
+
+        for (i, g) in self.input.grapheme_indices(true) {
+            if i == self.cursor {
+                cursor = Some((rows.len() - 1, row_width));
+            }
+            if g == "\n" {
+                rows.push(String::new());
+                row_width = 0;
+                continue;
+            }
+
+            let g_width = g.width();
+            if row_width + g_width > width {
+                rows.push(String::new());
+                row_width = 0;
+            }
+            rows.last_mut().unwrap().push_str(g);
+            row_width += g_width;
+        }
+
+        let mut cursor = cursor.unwrap_or((rows.len() - 1, row_width));
+        //Right after a full row means the start of the next one
+        if cursor.1 >= width {
+            cursor = (cursor.0 + 1, 0);
+            if rows.len() <= cursor.0 {
+                rows.push(String::new());
+            }
+        }
+
+        (rows, cursor)
+    }
+
+    //Scrolls just enough to keep the cursor row inside a box this tall
+    pub fn update_scroll(&mut self, cursor_row: usize, height: usize) {
+        if cursor_row < self.scroll {
+            self.scroll = cursor_row;
+        } else if height > 0 && cursor_row >= self.scroll + height {
+            self.scroll = cursor_row + 1 - height;
+        }
+    }
+
+    fn line_start(&self) -> usize {
+        match self.input[..self.cursor].rfind('\n') {
+            Some(i) => i + 1,
+            None => 0,
+        }
+    }
+
+    //Byte index of the nth grapheme of the line starting at start, or its end
+    fn column_in_line(&self, start: usize, column: usize) -> usize {
+        let line = match self.input[start..].find('\n') {
+            Some(i) => &self.input[start..start + i],
+            None => &self.input[start..],
+        };
+
+        match line.grapheme_indices(true).nth(column) {
+            Some((i, _)) => start + i,
+            None => start + line.len(),
+        }
+    }
+
+    fn prev_grapheme(&self) -> usize {
+        match self.input[..self.cursor].grapheme_indices(true).next_back() {
+            Some((i, _)) => i,
+            None => 0,
+        }
+    }
+
+    fn next_grapheme(&self) -> usize {
+        match self.input[self.cursor..].graphemes(true).next() {
+            Some(g) => self.cursor + g.len(),
+            None => self.input.len(),
+        }
+    }
+
+    //Start of the word left of the cursor, skipping spaces first
+    fn prev_word(&self) -> usize {
+        let mut position = self.cursor;
+        let mut graphemes = self.input[..self.cursor]
+            .grapheme_indices(true)
+            .rev()
+            .peekable();
+
+        while let Some((i, _)) = graphemes.next_if(|(_, g)| g.trim().is_empty()) {
+            position = i;
+        }
+        while let Some((i, _)) = graphemes.next_if(|(_, g)| !g.trim().is_empty()) {
+            position = i;
+        }
+
+        position
+    }
+
+    //End of the word right of the cursor, skipping spaces first
+    fn next_word(&self) -> usize {
+        let mut position = self.cursor;
+        let mut graphemes = self.input[self.cursor..].grapheme_indices(true).peekable();
+
+        while let Some((i, g)) = graphemes.next_if(|(_, g)| g.trim().is_empty()) {
+            position = self.cursor + i + g.len();
+        }
+        while let Some((i, g)) = graphemes.next_if(|(_, g)| !g.trim().is_empty()) {
+            position = self.cursor + i + g.len();
+        }
+
+        position
+    }
+}
+
+fn drafts_path() -> Option<PathBuf> {
+    Some(Config::dir()?.join("drafts.json"))
+}
+
+fn load_drafts() -> HashMap<String, String> {
+    let text = match drafts_path().map(std::fs::read_to_string) {
+        Some(Ok(v)) => v,
+        _ => return HashMap::new(),
+    };
+
+    serde_json::from_str(&text).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat_box(input: &str, cursor: usize) -> ChatBox {
+        let mut cbox = ChatBox::new();
+        cbox.input = input.to_string();
+        cbox.cursor = cursor;
+        cbox
+    }
+
+    #[test]
+    fn wrap_breaks_rows_at_width() {
+        let cbox = chat_box("abcdefg", 7);
+        let (rows, cursor) = cbox.wrap(3);
+        assert_eq!(rows, vec!["abc", "def", "g"]);
+        assert_eq!(cursor, (2, 1));
+    }
+
+    #[test]
+    fn wrap_keeps_newlines_and_finds_the_cursor() {
+        let cbox = chat_box("ab\ncd", 3);
+        let (rows, cursor) = cbox.wrap(10);
+        assert_eq!(rows, vec!["ab", "cd"]);
+        assert_eq!(cursor, (1, 0));
+    }
+
+    #[test]
+    fn wrap_moves_the_cursor_after_a_full_row_to_the_next() {
+        let cbox = chat_box("abc", 3);
+        let (rows, cursor) = cbox.wrap(3);
+        assert_eq!(rows, vec!["abc", ""]);
+        assert_eq!(cursor, (1, 0));
+    }
+
+    #[test]
+    fn wrap_counts_wide_characters_as_two_columns() {
+        let cbox = chat_box("日本語", 0);
+        let (rows, _) = cbox.wrap(4);
+        assert_eq!(rows, vec!["日本", "語"]);
+    }
+
+    #[test]
+    fn moving_by_character_steps_over_whole_graphemes() {
+        let mut cbox = chat_box("e\u{301}x", 0);
+        cbox.move_right();
+        assert_eq!(cbox.cursor, "e\u{301}".len());
+        cbox.move_left();
+        assert_eq!(cbox.cursor, 0);
+        cbox.move_left();
+        assert_eq!(cbox.cursor, 0);
+    }
+
+    #[test]
+    fn moving_by_word_skips_spaces_first() {
+        let mut cbox = chat_box("hello  big world", 16);
+        cbox.word_left();
+        assert_eq!(cbox.cursor, 11);
+        cbox.word_left();
+        assert_eq!(cbox.cursor, 7);
+        cbox.word_right();
+        assert_eq!(cbox.cursor, 10);
+        cbox.word_right();
+        assert_eq!(cbox.cursor, 16);
+    }
+
+    #[test]
+    fn delete_word_removes_the_word_before_the_cursor() {
+        let mut cbox = chat_box("hello world ", 12);
+        cbox.delete_word();
+        assert_eq!(cbox.input, "hello ");
+        assert_eq!(cbox.cursor, 6);
+    }
+
+    #[test]
+    fn home_end_and_delete_to_start_stay_on_the_line() {
+        let mut cbox = chat_box("one\ntwo three", 8);
+        cbox.home();
+        assert_eq!(cbox.cursor, 4);
+        cbox.end();
+        assert_eq!(cbox.cursor, 13);
+        cbox.delete_to_start();
+        assert_eq!(cbox.input, "one\n");
+    }
+
+    #[test]
+    fn up_and_down_keep_the_column() {
+        let mut cbox = chat_box("abcdef\nxy\nlonger", 5);
+        cbox.down();
+        assert_eq!(cbox.cursor, 9);
+        cbox.down();
+        assert_eq!(cbox.cursor, 12);
+        cbox.up();
+        assert_eq!(cbox.cursor, 9);
+    }
+
+    #[test]
+    fn history_goes_back_and_restores_the_draft() {
+        let mut cbox = chat_box("typing", 6);
+        cbox.history = vec!["first".to_string(), "second".to_string()];
+        cbox.history_previous();
+        assert_eq!(cbox.input, "second");
+        cbox.history_previous();
+        assert_eq!(cbox.input, "first");
+        cbox.history_next();
+        cbox.history_next();
+        assert_eq!(cbox.input, "typing");
+        assert_eq!(cbox.history_index, None);
+    }
+
+    #[test]
+    fn insert_str_turns_carriage_returns_into_newlines() {
+        let mut cbox = chat_box("", 0);
+        cbox.insert_str("a\r\nb\rc");
+        assert_eq!(cbox.input, "a\nb\nc");
+        assert_eq!(cbox.cursor, 5);
+    }
+}
@@ -0,0 +1,75 @@
+//Copying through the terminal with OSC 52, works over ssh too
+//Terminals that don't do OSC 52 can use a command like wl-copy instead
+
+use base64::Engine;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::ClipboardConfig;
+
+//What the copy menu can copy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CopyTarget {
+    Content,
+    Link,
+    AuthorId,
+    ChannelId,
+}
+
+pub fn copy(config: &ClipboardConfig, text: &str) {
+    let copied = match &config.command {
+        Some(command) => pipe(command, text),
+        None => false,
+    };
+    if !copied {
+        osc52(text);
+    }
+}
+
+fn osc52(text: &str) {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let mut stdout = std::io::stdout();
+    let _ = write!(stdout, "\x1b]52;c;{}\x07", encoded);
+    let _ = stdout.flush();
+}
+
+//Feeds text to the command's stdin, false if it didn't work out
+fn pipe(command: &str, text: &str) -> bool {
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+
+    //Dropping stdin closes it so the command knows it has everything
+    let written = match child.stdin.take() {
+        Some(mut stdin) => stdin.write_all(text.as_bytes()).is_ok(),
+        None => false,
+    };
+    if !written {
+        thread::spawn(move || child.wait());
+        return false;
+    }
+
+    //A missing command fails right away, give it a moment so OSC 52 can take over
+    let deadline = Instant::now() + Duration::from_millis(200);
+    while Instant::now() < deadline {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(_) => return false,
+        }
+    }
+
+    //xclip and friends can stay around to own the selection, don't wait for them
+    thread::spawn(move || child.wait());
+    true
+}
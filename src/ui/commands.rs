@@ -0,0 +1,616 @@
+//Client side slash commands, parsed before anything gets sent
+
+use serde_json::Value;
+
+use crate::api::data::{ApplicationCommand, CommandOption, OptionKind, SEND_MESSAGES};
+use crate::ui::channels::App;
+use crate::ui::completion::{self, Candidate};
+
+//name, arguments, what it does
+pub const COMMANDS: &[(&str, &str, &str)] = &[
+    (
+        "/nick",
+        "[name]",
+        "Change your nickname here, no name resets it",
+    ),
+    ("/me", "<text>", "Send text in italics"),
+    ("/shrug", "[text]", "Append ¯\\_(ツ)_/¯"),
+    ("/tableflip", "[text]", "Append (╯°□°)╯︵ ┻━┻"),
+    ("/status", "<online|idle|dnd|invisible>", "Set your status"),
+    ("/join", "<channel>", "Jump to a channel"),
+    (
+        "/upload",
+        "[paths]",
+        "Attach files, no paths opens a file picker",
+    ),
+    ("/search", "<text>", "Search messages in this server or DM"),
+    ("/leave", "", "Leave this server or group DM, asks first"),
+    ("/thread", "<join|leave>", "Join or leave the open thread"),
+    (
+        "/post",
+        "[+tag] <title> | <body>",
+        "Start a post in the open forum",
+    ),
+    (
+        "/publish",
+        "",
+        "Publish the selected message to servers following this channel",
+    ),
+    (
+        "/follow",
+        "<channel>",
+        "Get this channel's announcements in one of your channels",
+    ),
+    ("/help", "", "Show this list"),
+];
+
+pub const STATUSES: &[&str] = &["online", "idle", "dnd", "invisible"];
+
+pub enum SlashCommand {
+    //What /me, /shrug and /tableflip turn into, sent like a normal message
+    Send(String),
+    Nick(String),
+    Status(String),
+    Join(String),
+    Upload(String),
+    Search(String),
+    Leave,
+    //true to join
+    Thread(bool),
+    Post(String),
+    Publish,
+    Follow(String),
+    Help,
+    //A bot's command and everything typed after its name
+    Bot(ApplicationCommand, String),
+}
+
+//None when it isn't a command, Err when it's a broken one
+//Start with // to send something that starts with a slash
+//Ours come first, bots get whatever name is left
+pub fn parse(input: &str, bots: &[ApplicationCommand]) -> Option<Result<SlashCommand, String>> {
+    if !input.starts_with('/') || input.starts_with("//") {
+        return None;
+    }
+
+    let (name, args) = match input.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (input, ""),
+    };
+    let with_args = |text: &str| match args.is_empty() {
+        true => text.to_string(),
+        false => format!("{} {}", args, text),
+    };
+
+    let command = match name {
+        "/nick" => SlashCommand::Nick(args.to_string()),
+        "/me" if !args.is_empty() => SlashCommand::Send(format!("_{}_", args)),
+        "/shrug" => SlashCommand::Send(with_args("¯\\\\_(ツ)_/¯")),
+        "/tableflip" => SlashCommand::Send(with_args("(╯°□°)╯︵ ┻━┻")),
+        "/status" if STATUSES.contains(&args) => SlashCommand::Status(args.to_string()),
+        "/join" if !args.is_empty() => SlashCommand::Join(args.to_string()),
+        "/upload" => SlashCommand::Upload(args.to_string()),
+        "/search" if !args.is_empty() => SlashCommand::Search(args.to_string()),
+        "/leave" => SlashCommand::Leave,
+        "/thread" if args == "join" || args == "leave" => SlashCommand::Thread(args == "join"),
+        "/post" if !args.is_empty() => SlashCommand::Post(args.to_string()),
+        "/publish" => SlashCommand::Publish,
+        "/follow" if !args.is_empty() => SlashCommand::Follow(args.to_string()),
+        "/help" => SlashCommand::Help,
+        _ if !COMMANDS.iter().any(|(v, _, _)| *v == name) && bot(bots, name).is_some() => {
+            SlashCommand::Bot(bot(bots, name).unwrap().clone(), args.to_string())
+        }
+        _ => {
+            return Some(Err(match COMMANDS.iter().find(|(v, _, _)| *v == name) {
+                Some((name, usage, _)) => format!("Usage: {} {}", name, usage),
+                None => format!("Unknown command {}, /help lists them", name),
+            }))
+        }
+    };
+
+    Some(Ok(command))
+}
+
+//Tab completion for command names and their arguments
+//None when the normal @/#/: completion should handle it
+pub fn complete(app: &App, before_cursor: &str) -> Option<(usize, Vec<Candidate>)> {
+    if !before_cursor.starts_with('/') || before_cursor.starts_with("//") {
+        return None;
+    }
+
+    let bots = app.viewed_app_commands();
+    let (name, arg) = match before_cursor.split_once(' ') {
+        Some(v) => v,
+        None => {
+            let ours = COMMANDS
+                .iter()
+                .filter(|(name, _, _)| name.starts_with(before_cursor))
+                .filter(|(name, _, _)| offered(app, name))
+                .map(|(name, usage, description)| Candidate {
+                    label: format!("{} {} - {}", name, usage, description),
+                    text: name.to_string(),
+                    markup: None,
+                });
+            let theirs = bots
+                .iter()
+                .filter(|command| format!("/{}", command.name).starts_with(before_cursor))
+                .filter(|command| !COMMANDS.iter().any(|(v, _, _)| v[1..] == command.name))
+                .map(|command| Candidate {
+                    label: format!(
+                        "/{} - {} ({})",
+                        command.name, command.description, command.application
+                    ),
+                    text: format!("/{}", command.name),
+                    markup: None,
+                });
+            return Some((0, ours.chain(theirs).collect()));
+        }
+    };
+    let start = name.len() + 1;
+
+    let candidates: Vec<Candidate> = match name {
+        "/status" => STATUSES
+            .iter()
+            .filter(|status| status.starts_with(arg))
+            .map(|status| plain(status.to_string()))
+            .collect(),
+        //Tags go before the title
+        "/post" => {
+            let word_start = arg.rfind(' ').map_or(0, |i| i + 1);
+            let word = &arg[word_start..];
+            let tags = match (word.strip_prefix('+'), app.viewed_forum()) {
+                (Some(typed), Some(forum)) => forum
+                    .available_tags
+                    .iter()
+                    .filter(|tag| tag.name.to_lowercase().starts_with(&typed.to_lowercase()))
+                    .map(|tag| Candidate {
+                        label: tag.display(),
+                        text: format!("+{}", tag.name),
+                        markup: None,
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            return Some((start + word_start, tags));
+        }
+        "/thread" => ["join", "leave"]
+            .iter()
+            .filter(|v| v.starts_with(arg))
+            .map(|v| plain(v.to_string()))
+            .collect(),
+        "/join" => {
+            let query = arg.trim_start_matches('#').to_lowercase();
+            app.viewed_guild()
+                .map(|guild| guild.channels.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .filter(|channel| channel.name.to_lowercase().contains(&query))
+                .map(|channel| plain(format!("#{}", channel.name)))
+                .collect()
+        }
+        "/follow" => {
+            let query = arg.trim_start_matches('#').to_lowercase();
+            app.follow_targets()
+                .into_iter()
+                .filter(|target| target.label.to_lowercase().contains(&query))
+                .map(|target| plain(target.label))
+                .collect()
+        }
+        //Only the last path is being completed
+        "/upload" => {
+            let start = start + arg.rfind(' ').map_or(0, |i| i + 1);
+            return Some((start, paths(&before_cursor[start..])));
+        }
+        _ => match bot(bots, name) {
+            Some(command) => return Some(complete_options(app, &command.options, arg, start)),
+            None => return None,
+        },
+    };
+
+    Some((start, candidates))
+}
+
+//Announcement commands only show up where they can work
+fn offered(app: &App, name: &str) -> bool {
+    let channel = match app.viewed_channel() {
+        Some(v) => v,
+        None => return true,
+    };
+    match name {
+        "/publish" => channel.is_announcement() && app.can(&channel, SEND_MESSAGES),
+        "/follow" => channel.is_announcement(),
+        _ => true,
+    }
+}
+
+fn bot<'a>(bots: &'a [ApplicationCommand], name: &str) -> Option<&'a ApplicationCommand> {
+    let name = name.strip_prefix('/')?;
+    bots.iter().find(|command| command.name == name)
+}
+
+//Options come as name:value, subcommands as plain words before them
+//A command with one required option also takes it without the name
+pub fn bot_options(options: &[CommandOption], args: &str) -> Result<Vec<Value>, String> {
+    if options.iter().any(|option| option.is_subcommand()) {
+        let (word, rest) = args.split_once(' ').unwrap_or((args, ""));
+        let names = || {
+            let names: Vec<&str> = options.iter().map(|v| v.name.as_str()).collect();
+            names.join(", ")
+        };
+        let sub = options
+            .iter()
+            .find(|option| option.is_subcommand() && option.name == word)
+            .ok_or_else(|| format!("Pick one of: {}", names()))?;
+
+        return Ok(vec![serde_json::json!({
+            "type": sub.kind.id(),
+            "name": sub.name,
+            "options": bot_options(&sub.options, rest.trim())?,
+        })]);
+    }
+
+    let positional = match options.iter().filter(|v| v.required).count() {
+        1 => options.iter().find(|v| v.required),
+        _ => None,
+    };
+    let mut given: Vec<(&CommandOption, String)> = Vec::new();
+    for word in args.split(' ').filter(|v| !v.is_empty()) {
+        let named = word.split_once(':').and_then(|(name, value)| {
+            let option = options.iter().find(|v| v.name == name)?;
+            match given.iter().any(|(v, _)| v.name == name) {
+                true => None,
+                false => Some((option, value)),
+            }
+        });
+
+        match (named, given.last_mut()) {
+            (Some((option, value)), _) => given.push((option, value.to_string())),
+            //Values can have spaces, everything until the next name: belongs to it
+            (None, Some((_, value))) => {
+                value.push(' ');
+                value.push_str(word);
+            }
+            (None, None) => match positional {
+                Some(option) => given.push((option, word.to_string())),
+                None => return Err("Options go like name:value, Tab lists them".to_string()),
+            },
+        }
+    }
+
+    if let Some(missing) = options
+        .iter()
+        .find(|option| option.required && !given.iter().any(|(v, _)| v.name == option.name))
+    {
+        return Err(format!("Missing {}:{}", missing.name, missing.kind.name()));
+    }
+
+    given
+        .into_iter()
+        .map(|(option, text)| {
+            Ok(serde_json::json!({
+                "type": option.kind.id(),
+                "name": option.name,
+                "value": option_value(option, text.trim())?,
+            }))
+        })
+        .collect()
+}
+
+//Typed text to whatever json type the option wants
+fn option_value(option: &CommandOption, text: &str) -> Result<Value, String> {
+    if !option.choices.is_empty() {
+        let choice = option.choices.iter().find(|(name, value)| {
+            name.eq_ignore_ascii_case(text)
+                || value.as_str() == Some(text)
+                || (value.is_number() && value.as_f64() == text.parse().ok())
+        });
+        return match choice {
+            Some((_, value)) => Ok(value.clone()),
+            None => {
+                let names: Vec<&str> = option.choices.iter().map(|(v, _)| v.as_str()).collect();
+                Err(format!(
+                    "{} has to be one of: {}",
+                    option.name,
+                    names.join(", ")
+                ))
+            }
+        };
+    }
+
+    let invalid = || format!("{} needs a {}", option.name, option.kind.name());
+    let value = match option.kind {
+        OptionKind::String => Value::from(text),
+        OptionKind::Integer => Value::from(text.parse::<i64>().map_err(|_| invalid())?),
+        OptionKind::Number => Value::from(text.parse::<f64>().map_err(|_| invalid())?),
+        OptionKind::Boolean => match text.to_lowercase().as_str() {
+            "true" | "yes" | "on" | "1" => Value::from(true),
+            "false" | "no" | "off" | "0" => Value::from(false),
+            _ => return Err(invalid()),
+        },
+        //Mentions from Tab completion, or a bare id
+        OptionKind::User | OptionKind::Channel | OptionKind::Role | OptionKind::Mentionable => {
+            let id = text
+                .trim_start_matches('<')
+                .trim_start_matches(['@', '#', '!', '&'])
+                .trim_end_matches('>');
+            if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit()) {
+                return Err(format!("{}, Tab completes them", invalid()));
+            }
+            Value::from(id)
+        }
+        OptionKind::Attachment => return Err("File options aren't supported".to_string()),
+        OptionKind::SubCommand | OptionKind::SubCommandGroup => return Err(invalid()),
+    };
+    Ok(value)
+}
+
+//Subcommand names, option names, choices, or a mention inside an option
+//start is where arg begins in the input
+fn complete_options(
+    app: &App,
+    options: &[CommandOption],
+    arg: &str,
+    start: usize,
+) -> (usize, Vec<Candidate>) {
+    if options.iter().any(|option| option.is_subcommand()) {
+        if let Some((word, rest)) = arg.split_once(' ') {
+            return match options.iter().find(|v| v.name == word) {
+                Some(sub) => complete_options(app, &sub.options, rest, start + word.len() + 1),
+                None => (start, Vec::new()),
+            };
+        }
+        let candidates = options
+            .iter()
+            .filter(|option| option.name.starts_with(arg))
+            .map(|option| Candidate {
+                label: format!("{} - {}", option.name, option.description),
+                text: option.name.clone(),
+                markup: None,
+            })
+            .collect();
+        return (start, candidates);
+    }
+
+    let word_start = arg.rfind(' ').map_or(0, |i| i + 1);
+    let word = &arg[word_start..];
+    let start = start + word_start;
+
+    if let Some((name, value)) = word.split_once(':') {
+        let option = match options.iter().find(|v| v.name == name) {
+            Some(v) => v,
+            None => return (start, Vec::new()),
+        };
+        let value_start = start + name.len() + 1;
+        if option.choices.is_empty() {
+            return (value_start, completion::candidates(app, value));
+        }
+        let candidates = option
+            .choices
+            .iter()
+            .filter(|(choice, _)| choice.to_lowercase().starts_with(&value.to_lowercase()))
+            .map(|(choice, _)| plain(choice.clone()))
+            .collect();
+        return (value_start, candidates);
+    }
+
+    let candidates = options
+        .iter()
+        .filter(|option| option.name.starts_with(word))
+        .filter(|option| !arg.contains(&format!("{}:", option.name)))
+        .map(|option| {
+            let required = match option.required {
+                true => " (required)",
+                false => "",
+            };
+            Candidate {
+                label: format!(
+                    "{}:{}{} - {}",
+                    option.name,
+                    option.kind.name(),
+                    required,
+                    option.description
+                ),
+                text: format!("{}:", option.name),
+                markup: None,
+            }
+        })
+        .collect();
+    (start, candidates)
+}
+
+fn plain(text: String) -> Candidate {
+    Candidate {
+        label: text.clone(),
+        text,
+        markup: None,
+    }
+}
+
+//Files and folders starting with what's typed, folders end in / so Tab can keep going
+fn paths(typed: &str) -> Vec<Candidate> {
+    let (dir, prefix) = match typed.rfind('/') {
+        Some(i) => (&typed[..=i], &typed[i + 1..]),
+        None => ("", typed),
+    };
+    let read_from = match dir {
+        "" => super::upload::expand_home("."),
+        dir => super::upload::expand_home(dir),
+    };
+
+    let mut found: Vec<Candidate> = match std::fs::read_dir(read_from) {
+        Ok(v) => v
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+                let slash = match entry.path().is_dir() {
+                    true => "/",
+                    false => "",
+                };
+                Some(plain(format!("{}{}{}", dir, name, slash)))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    found.sort_by(|a, b| a.text.cmp(&b.text));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn option(kind: u64, name: &str, required: bool) -> Value {
+        json!({ "type": kind, "name": name, "description": "", "required": required })
+    }
+
+    fn command(name: &str, options: Value) -> ApplicationCommand {
+        ApplicationCommand {
+            id: "1".to_string(),
+            application_id: "2".to_string(),
+            version: "3".to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            application: "bot".to_string(),
+            options: CommandOption::from_list(&options),
+            raw: Value::Null,
+        }
+    }
+
+    #[test]
+    fn parse_leaves_messages_alone() {
+        assert!(parse("hello", &[]).is_none());
+        assert!(parse("//not a command", &[]).is_none());
+    }
+
+    #[test]
+    fn parse_our_commands() {
+        assert!(matches!(
+            parse("/me waves", &[]),
+            Some(Ok(SlashCommand::Send(text))) if text == "_waves_"
+        ));
+        assert!(matches!(
+            parse("/shrug ok", &[]),
+            Some(Ok(SlashCommand::Send(text))) if text == "ok ¯\\\\_(ツ)_/¯"
+        ));
+        assert!(matches!(
+            parse("/status idle", &[]),
+            Some(Ok(SlashCommand::Status(status))) if status == "idle"
+        ));
+        assert!(matches!(
+            parse("/leave", &[]),
+            Some(Ok(SlashCommand::Leave))
+        ));
+        assert!(
+            matches!(parse("/nick", &[]), Some(Ok(SlashCommand::Nick(name))) if name.is_empty())
+        );
+    }
+
+    #[test]
+    fn parse_usage_errors() {
+        assert!(matches!(
+            parse("/status away", &[]),
+            Some(Err(e)) if e == "Usage: /status <online|idle|dnd|invisible>"
+        ));
+        assert!(matches!(parse("/me", &[]), Some(Err(e)) if e == "Usage: /me <text>"));
+        assert!(matches!(
+            parse("/nope", &[]),
+            Some(Err(e)) if e == "Unknown command /nope, /help lists them"
+        ));
+    }
+
+    #[test]
+    fn parse_bot_commands_after_ours() {
+        let bots = [command("roll", json!([])), command("help", json!([]))];
+        assert!(matches!(
+            parse("/roll 2d6", &bots),
+            Some(Ok(SlashCommand::Bot(command, args))) if command.name == "roll" && args == "2d6"
+        ));
+        assert!(matches!(
+            parse("/help", &bots),
+            Some(Ok(SlashCommand::Help))
+        ));
+    }
+
+    #[test]
+    fn bot_options_named_values() {
+        let options = CommandOption::from_list(&json!([
+            option(3, "text", true),
+            option(4, "count", false),
+            option(5, "loud", false),
+        ]));
+        assert_eq!(
+            bot_options(&options, "text:hello there count:3 loud:yes").unwrap(),
+            [
+                json!({ "type": 3, "name": "text", "value": "hello there" }),
+                json!({ "type": 4, "name": "count", "value": 3 }),
+                json!({ "type": 5, "name": "loud", "value": true }),
+            ]
+        );
+    }
+
+    #[test]
+    fn bot_options_single_required_is_positional() {
+        let options = CommandOption::from_list(&json!([option(3, "query", true)]));
+        assert_eq!(
+            bot_options(&options, "cats and dogs").unwrap(),
+            [json!({ "type": 3, "name": "query", "value": "cats and dogs" })]
+        );
+    }
+
+    #[test]
+    fn bot_options_errors() {
+        let options =
+            CommandOption::from_list(&json!([option(4, "count", true), option(6, "who", true),]));
+        assert_eq!(
+            bot_options(&options, "count:3").unwrap_err(),
+            "Missing who:@user"
+        );
+        assert_eq!(
+            bot_options(&options, "count:many who:<@1>").unwrap_err(),
+            "count needs a integer"
+        );
+        assert_eq!(
+            bot_options(&options, "3").unwrap_err(),
+            "Options go like name:value, Tab lists them"
+        );
+        assert_eq!(
+            bot_options(&options, "count:3 who:<@!123>").unwrap()[1]["value"],
+            "123"
+        );
+    }
+
+    #[test]
+    fn bot_options_choices_and_subcommands() {
+        let mut color = option(3, "color", true);
+        color["choices"] = json!([
+            { "name": "Red", "value": "r" },
+            { "name": "Blue", "value": "b" },
+        ]);
+        let options = CommandOption::from_list(&json!([
+            { "type": 1, "name": "paint", "options": [color] },
+            { "type": 1, "name": "clear" },
+        ]));
+
+        assert_eq!(
+            bot_options(&options, "paint color:red").unwrap(),
+            [json!({
+                "type": 1,
+                "name": "paint",
+                "options": [{ "type": 3, "name": "color", "value": "r" }],
+            })]
+        );
+        assert_eq!(
+            bot_options(&options, "paint color:green").unwrap_err(),
+            "color has to be one of: Red, Blue"
+        );
+        assert_eq!(
+            bot_options(&options, "erase").unwrap_err(),
+            "Pick one of: paint, clear"
+        );
+    }
+}
@@ -0,0 +1,256 @@
+//Tab completion for @users, #channels and :emoji: in the chat box
+
+use std::collections::{HashMap, HashSet};
+
+use crate::api::data::{Channel, Guild, MemberListItem, User};
+use crate::ui::channels::App;
+
+//Most that get offered at once
+const MAX_CANDIDATES: usize = 30;
+
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    //What the popup shows
+    pub label: String,
+    //What goes in the chat box
+    pub text: String,
+    //What text turns into when sent, None if it's sent as is
+    pub markup: Option<String>,
+}
+
+//A completion in progress, Tab cycles through candidates until another key is pressed
+pub struct Completion {
+    //Byte index where the word being completed starts
+    pub start: usize,
+    pub candidates: Vec<Candidate>,
+    pub index: usize,
+}
+
+impl Completion {
+    pub fn get_candidate(&self) -> &Candidate {
+        &self.candidates[self.index]
+    }
+}
+
+//word includes the trigger, so "@bo", "#gen" or ":thu"
+pub fn candidates(app: &App, word: &str) -> Vec<Candidate> {
+    let mut chars = word.chars();
+    let trigger = chars.next();
+    let query = chars.as_str().to_lowercase();
+
+    let channel = app.viewed_channel();
+    let guild = app.viewed_guild();
+
+    let candidates = match trigger {
+        Some('@') => match &channel {
+            Some(channel) => users(app, channel, &query),
+            None => Vec::new(),
+        },
+        Some('#') => match guild {
+            Some(guild) => channels(guild, &query),
+            None => Vec::new(),
+        },
+        Some(':') => emoji(guild, &query),
+        _ => Vec::new(),
+    };
+
+    candidates.into_iter().take(MAX_CANDIDATES).collect()
+}
+
+//Recent authors first, then whoever else we know is in the channel
+fn users(app: &App, channel: &Channel, query: &str) -> Vec<Candidate> {
+    let mut seen = HashSet::new();
+    let mut found: Vec<(User, String)> = Vec::new();
+    let mut add = |user: &User, name: &str| {
+        if seen.insert(user.id.clone()) {
+            found.push((user.clone(), name.to_string()));
+        }
+    };
+
+    if let Some(messages) = app.loaded_channels.get(channel) {
+        for message in messages.items.iter().rev() {
+            let (name, _) = app.author_name(message, &channel.guild_id);
+            add(&message.user, &name);
+        }
+    }
+    if app.member_list_guild == channel.guild_id {
+        for item in &app.member_list {
+            if let MemberListItem::Member(member) = item {
+                add(&member.user, member.display_name());
+            }
+        }
+    }
+    for ((guild_id, _), member) in &app.members {
+        if *guild_id == channel.guild_id {
+            add(&member.user, member.display_name());
+        }
+    }
+
+    let found = found.into_iter().filter_map(|(user, name)| {
+        let rank = rank(query, &[&user.name, &name])?;
+        let label = match name == user.name {
+            true => format!("@{}", user.name),
+            false => format!("{} (@{})", name, user.name),
+        };
+        let candidate = Candidate {
+            label,
+            text: format!("@{}", user.name),
+            markup: Some(format!("<@{}>", user.id)),
+        };
+        Some((rank, candidate))
+    });
+
+    sorted(found)
+}
+
+fn channels(guild: &Guild, query: &str) -> Vec<Candidate> {
+    let found = guild.channels.iter().filter_map(|channel| {
+        let rank = rank(query, &[&channel.name])?;
+        let candidate = Candidate {
+            label: format!("#{}", channel.name),
+            text: format!("#{}", channel.name),
+            markup: Some(format!("<#{}>", channel.id)),
+        };
+        Some((rank, candidate))
+    });
+
+    sorted(found)
+}
+
+//The guild's own emoji go before the unicode ones
+pub fn emoji(guild: Option<&Guild>, query: &str) -> Vec<Candidate> {
+    let custom = guild
+        .map(|guild| guild.emojis.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|emoji| {
+            let rank = rank(query, &[&emoji.name])?;
+            let candidate = Candidate {
+                label: format!(":{}: (custom)", emoji.name),
+                text: format!(":{}:", emoji.name),
+                markup: Some(emoji.markup()),
+            };
+            Some((rank, candidate))
+        });
+
+    let unicode = emojis::iter().flat_map(|emoji| {
+        emoji.shortcodes().filter_map(move |shortcode| {
+            let rank = rank(query, &[shortcode])?;
+            let candidate = Candidate {
+                label: format!("{} :{}:", emoji.as_str(), shortcode),
+                text: emoji.as_str().to_string(),
+                markup: None,
+            };
+            Some((rank, candidate))
+        })
+    });
+
+    sorted(custom.chain(unicode))
+}
+
+//0 when one of the names starts with the query, 1 when it's somewhere inside, None otherwise
+fn rank(query: &str, names: &[&str]) -> Option<u8> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let name = name.to_lowercase();
+            if name.starts_with(query) {
+                Some(0)
+            } else if name.contains(query) {
+                Some(1)
+            } else {
+                None
+            }
+        })
+        .min()
+}
+
+//Stable, so things stay in the order they were found within a rank
+fn sorted(found: impl Iterator<Item = (u8, Candidate)>) -> Vec<Candidate> {
+    let mut found: Vec<(u8, Candidate)> = found.collect();
+    found.sort_by_key(|(rank, _)| *rank);
+    found.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+//Swaps the readable @name/#channel/:emoji: text for what discord expects
+//Only whole words, so @bob doesn't eat the start of @bobby and foo@bob stays as it is
+pub fn to_markup(content: &str, markups: &HashMap<String, String>) -> String {
+    let mut content = content.to_string();
+    //Longest first for the same reason
+    let mut markups: Vec<(&String, &String)> = markups.iter().collect();
+    markups.sort_by_key(|(text, _)| std::cmp::Reverse(text.len()));
+
+    for (text, markup) in markups {
+        let mut result = String::new();
+        let mut rest = content.as_str();
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        while let Some(i) = rest.find(text.as_str()) {
+            let after = &rest[i + text.len()..];
+            //What came before includes the part of content already handled
+            let before = rest[..i].chars().last().or(result.chars().last());
+            let whole_word = !after.chars().next().is_some_and(is_word)
+                && !before.is_some_and(is_word);
+
+            result.push_str(&rest[..i]);
+            match whole_word {
+                true => result.push_str(markup),
+                false => result.push_str(text),
+            }
+            rest = after;
+        }
+        result.push_str(rest);
+        content = result;
+    }
+
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markups(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(text, markup)| (text.to_string(), markup.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn to_markup_replaces_completed_names() {
+        let markups = markups(&[("@bob", "<@1>"), ("#general", "<#2>")]);
+        assert_eq!(
+            to_markup("hi @bob, see #general", &markups),
+            "hi <@1>, see <#2>"
+        );
+    }
+
+    #[test]
+    fn to_markup_only_takes_whole_words() {
+        let markups = markups(&[("@bob", "<@1>")]);
+        assert_eq!(to_markup("@bobby", &markups), "@bobby");
+        assert_eq!(to_markup("foo@bob", &markups), "foo@bob");
+        assert_eq!(to_markup("@bob_", &markups), "@bob_");
+        assert_eq!(to_markup("(@bob)", &markups), "(<@1>)");
+    }
+
+    #[test]
+    fn to_markup_prefers_the_longest_name() {
+        let markups = markups(&[("@bob", "<@1>"), ("@bob smith", "<@2>")]);
+        assert_eq!(to_markup("@bob smith and @bob", &markups), "<@2> and <@1>");
+    }
+
+    #[test]
+    fn to_markup_replaces_every_occurrence() {
+        let markups = markups(&[(":party:", "<:party:3>")]);
+        assert_eq!(
+            to_markup(":party: :party:", &markups),
+            "<:party:3> <:party:3>"
+        );
+    }
+
+    #[test]
+    fn to_markup_leaves_text_alone_without_markups() {
+        assert_eq!(to_markup("@bob", &HashMap::new()), "@bob");
+    }
+}
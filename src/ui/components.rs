@@ -0,0 +1,109 @@
+//Buttons and select menus under bot messages, picked with the keyboard
+
+use crate::api::data::{Component, Msg, SelectMenu, SelectOption};
+
+use super::stateful_list::StatefulList;
+
+//Which component of which message has focus
+pub struct ComponentFocus {
+    pub channel_id: String,
+    pub message_id: String,
+    //Counting left to right, then row by row
+    pub index: usize,
+}
+
+impl ComponentFocus {
+    pub fn new(message: &Msg) -> ComponentFocus {
+        ComponentFocus {
+            channel_id: message.channel_id.clone(),
+            message_id: message.id.clone(),
+            index: 0,
+        }
+    }
+
+    //Wraps around both ways
+    pub fn step(&mut self, count: usize, forward: bool) {
+        if count == 0 {
+            return;
+        }
+        self.index = match forward {
+            true => (self.index + 1) % count,
+            false => (self.index + count - 1) % count,
+        };
+    }
+}
+
+//All of a message's components in focus order
+pub fn flatten(message: &Msg) -> Vec<&Component> {
+    message.components.iter().flatten().collect()
+}
+
+//Popup listing a menu's options, space marks several when the menu allows it
+pub struct SelectPicker {
+    pub message: Msg,
+    pub menu: SelectMenu,
+    pub options: StatefulList<SelectOption>,
+    //Values of the marked options
+    pub chosen: Vec<String>,
+}
+
+impl SelectPicker {
+    pub fn new(message: Msg, menu: SelectMenu) -> SelectPicker {
+        let mut options = StatefulList::from(menu.options.clone());
+        if !options.items.is_empty() {
+            options.state.select(Some(0));
+        }
+        let chosen = menu
+            .options
+            .iter()
+            .filter(|option| option.default)
+            .map(|option| option.value.clone())
+            .collect();
+
+        SelectPicker {
+            message,
+            menu,
+            options,
+            chosen,
+        }
+    }
+
+    pub fn multiple(&self) -> bool {
+        self.menu.max_values > 1
+    }
+
+    pub fn toggle(&mut self) {
+        let value = match self.highlighted() {
+            Some(v) => v.value.clone(),
+            None => return,
+        };
+
+        if let Some(i) = self.chosen.iter().position(|v| *v == value) {
+            self.chosen.remove(i);
+        } else if (self.chosen.len() as u64) < self.menu.max_values {
+            self.chosen.push(value);
+        }
+    }
+
+    //What gets sent, menus with one pick just take the highlighted option
+    pub fn values(&self) -> Result<Vec<String>, String> {
+        let values = match (self.multiple(), self.highlighted()) {
+            (false, Some(option)) => vec![option.value.clone()],
+            _ => self.chosen.clone(),
+        };
+
+        let count = values.len() as u64;
+        if count < self.menu.min_values {
+            return Err(format!("Pick at least {}", self.menu.min_values));
+        }
+        if count > self.menu.max_values {
+            return Err(format!("Pick at most {}", self.menu.max_values));
+        }
+        Ok(values)
+    }
+
+    fn highlighted(&self) -> Option<&SelectOption> {
+        let index = self.options.state.selected()?;
+        self.options.items.get(index)
+    }
+}
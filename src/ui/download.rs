@@ -0,0 +1,131 @@
+//Saving attachments to disk, a Transfer like uploads
+
+use std::path::{Path, PathBuf};
+
+use crate::api::data::{Attachment, Connection};
+use crate::api::wrapper;
+use crate::config::DownloadConfig;
+
+use super::opener;
+use super::transfer::{self, Transfer};
+use super::upload::expand_home;
+
+pub type Download = Transfer<Vec<PathBuf>>;
+
+//Resolves to where the files ended up
+pub fn start(
+    conn: &Connection,
+    config: &DownloadConfig,
+    attachments: Vec<Attachment>,
+) -> Result<Download, String> {
+    let dir = download_dir(config);
+    std::fs::create_dir_all(&dir)
+        .map_err(|err| format!("Couldn't create {}: {}", dir.display(), err))?;
+
+    let names: Vec<String> = attachments
+        .iter()
+        .map(|attachment| attachment.filename.clone())
+        .collect();
+    let total = attachments.iter().map(|attachment| attachment.size).sum();
+
+    let conn = conn.clone();
+    Ok(Transfer::spawn(
+        "Downloading",
+        transfer::label(&names),
+        total,
+        move |received| {
+            let mut paths = Vec::new();
+            for attachment in &attachments {
+                let path = unique_path(&dir, &attachment.filename);
+                wrapper::download_file(&conn, &attachment.url, &path, received.clone())?;
+                paths.push(path);
+            }
+            Ok(paths)
+        },
+    ))
+}
+
+pub fn download_dir(config: &DownloadConfig) -> PathBuf {
+    match &config.dir {
+        Some(v) => expand_home(v),
+        None => expand_home("~/Downloads"),
+    }
+}
+
+//Hands the file to the configured command, or the system's default app
+pub fn open(config: &DownloadConfig, path: &Path) {
+    opener::open_with(config.open_command.as_deref(), path);
+}
+
+//cat.png, then cat (1).png, cat (2).png...
+fn unique_path(dir: &Path, filename: &str) -> PathBuf {
+    //Never trust a name to stay inside the folder
+    let filename = Path::new(filename)
+        .file_name()
+        .map(|v| v.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string());
+
+    let path = dir.join(&filename);
+    if !path.exists() {
+        return path;
+    }
+
+    let (stem, extension) = match filename.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (filename.as_str(), String::new()),
+    };
+    (1..)
+        .map(|i| dir.join(format!("{} ({}){}", stem, i, extension)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A fresh folder per test so they can run at the same time
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("disrust-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn unique_path_numbers_taken_names() {
+        let dir = temp_dir("numbers");
+        assert_eq!(unique_path(&dir, "cat.png"), dir.join("cat.png"));
+
+        std::fs::write(dir.join("cat.png"), "").unwrap();
+        assert_eq!(unique_path(&dir, "cat.png"), dir.join("cat (1).png"));
+
+        std::fs::write(dir.join("cat (1).png"), "").unwrap();
+        assert_eq!(unique_path(&dir, "cat.png"), dir.join("cat (2).png"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unique_path_without_extension() {
+        let dir = temp_dir("extension");
+        std::fs::write(dir.join("README"), "").unwrap();
+        std::fs::write(dir.join(".bashrc"), "").unwrap();
+
+        assert_eq!(unique_path(&dir, "README"), dir.join("README (1)"));
+        assert_eq!(unique_path(&dir, ".bashrc"), dir.join(".bashrc (1)"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unique_path_stays_in_the_folder() {
+        let dir = temp_dir("traversal");
+        assert_eq!(unique_path(&dir, "../../etc/passwd"), dir.join("passwd"));
+        assert_eq!(unique_path(&dir, "/tmp/cat.png"), dir.join("cat.png"));
+        assert_eq!(unique_path(&dir, ".."), dir.join("file"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+//Writing messages in $EDITOR, the TUI gets out of the way while it runs
+
+use crossterm::{
+    cursor::Show,
+    event::{
+        DisableBracketedPaste, EnableBracketedPaste, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+//The draft's file, deleted however compose returns
+struct DraftFile {
+    path: PathBuf,
+}
+
+impl DraftFile {
+    //Never reuses a file someone else put there (or a symlink to one), and only we can read it
+    fn create() -> Option<(DraftFile, File)> {
+        for attempt in 0..8 {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |v| v.subsec_nanos());
+            let name = format!("disrust-{}-{}-{}.md", std::process::id(), nanos, attempt);
+            let path = std::env::temp_dir().join(name);
+
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+            match options.open(&path) {
+                Ok(file) => return Some((DraftFile { path }, file)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(_) => return None,
+            }
+        }
+        None
+    }
+}
+
+impl Drop for DraftFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+//Opens the draft in $VISUAL/$EDITOR (vi if neither is set)
+//None if the editor couldn't start or exited with an error, the draft stays as it was
+pub fn compose(draft: &str) -> Option<String> {
+    let (draft_file, mut file) = DraftFile::create()?;
+    file.write_all(draft.as_bytes()).ok()?;
+    drop(file);
+    let path = &draft_file.path;
+
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    suspend();
+    //Through sh so things like EDITOR="code --wait" work
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path)
+        .status();
+    resume();
+
+    let text = match status {
+        Ok(v) if v.success() => std::fs::read_to_string(path).ok(),
+        _ => None,
+    };
+
+    //Editors like to end the file with a newline, nobody wants it sent
+    Some(text?.trim_end_matches(['\n', '\r']).to_string())
+}
+
+//Hands the terminal back the way a normal program expects it
+fn suspend() {
+    let _ = execute!(
+        io::stdout(),
+        PopKeyboardEnhancementFlags,
+        DisableBracketedPaste,
+        LeaveAlternateScreen,
+        Show
+    );
+    let _ = disable_raw_mode();
+}
+
+fn resume() {
+    let _ = enable_raw_mode();
+    let _ = execute!(
+        io::stdout(),
+        EnterAlternateScreen,
+        EnableBracketedPaste,
+        PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+    );
+}
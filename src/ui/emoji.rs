@@ -0,0 +1,278 @@
+//:shortcode: conversion and the emoji picker popup
+//The shortcode table is gemoji's, bundled by the emojis crate so it works offline
+
+use crate::api::data::{Guild, Msg, ReactionEmoji};
+
+use super::completion::{self, Candidate};
+use super::hints;
+use super::stateful_list::StatefulList;
+
+//The picker can show a lot more than tab completion
+const MAX_RESULTS: usize = 200;
+
+pub struct EmojiPicker {
+    pub query: String,
+    pub results: StatefulList<Candidate>,
+    //The message being reacted to, None when picking for the chat box
+    pub react_to: Option<Msg>,
+}
+
+impl EmojiPicker {
+    pub fn new(react_to: Option<Msg>) -> EmojiPicker {
+        EmojiPicker {
+            query: String::new(),
+            results: StatefulList::from(Vec::new()),
+            react_to,
+        }
+    }
+
+    //Reruns the search, the guild's custom emoji come first
+    pub fn update(&mut self, guild: Option<&Guild>) {
+        let results: Vec<Candidate> = completion::emoji(guild, &self.query.to_lowercase())
+            .into_iter()
+            .take(MAX_RESULTS)
+            .collect();
+        self.results = StatefulList::from(results);
+        if !self.results.items.is_empty() {
+            self.results.state.select(Some(0));
+        }
+    }
+
+    pub fn get_candidate(&self) -> Option<Candidate> {
+        let index = self.results.state.selected()?;
+        self.results.items.get(index).cloned()
+    }
+}
+
+//Before sending, :thumbsup: becomes 👍 and the guild's own :emoji: become <:emoji:id>
+//Code and links are left as typed
+pub fn from_shortcodes(content: &str, guild: Option<&Guild>) -> String {
+    let lookup = |name: &str| {
+        if let Some(emoji) = emojis::get_by_shortcode(name) {
+            return Some(emoji.as_str().to_string());
+        }
+        let custom = guild?.emojis.iter().find(|emoji| emoji.name == name)?;
+        Some(custom.markup())
+    };
+
+    let mut result = String::new();
+    let mut last = 0;
+    for (start, end) in untouched_spans(content) {
+        result.push_str(&replace_shortcodes(&content[last..start], lookup));
+        result.push_str(&content[start..end]);
+        last = end;
+    }
+    result.push_str(&replace_shortcodes(&content[last..], lookup));
+    result
+}
+
+//Byte ranges of `code`, ```code blocks``` and links, in order
+fn untouched_spans(content: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut from = 0;
+    while let Some(open) = content[from..].find('`').map(|i| from + i) {
+        let fence = match content[open..].starts_with("```") {
+            true => "```",
+            false => "`",
+        };
+        let after = open + fence.len();
+        //Discord shows an unclosed backtick as it is
+        match content[after..].find(fence) {
+            Some(close) => {
+                let end = after + close + fence.len();
+                spans.push((open, end));
+                from = end;
+            }
+            None => from = after,
+        }
+    }
+
+    //find_urls hands back slices of content, their offset is where they start
+    for url in hints::find_urls(content) {
+        let start = url.as_ptr() as usize - content.as_ptr() as usize;
+        let in_code = spans
+            .iter()
+            .any(|&(open, end)| open <= start && start < end);
+        if !in_code {
+            spans.push((start, start + url.len()));
+        }
+    }
+
+    spans.sort();
+    spans
+}
+
+//For showing received messages, custom emoji can't be drawn so they stay as :name:
+pub fn display(content: &str) -> String {
+    let content = replace_shortcodes(content, |name| {
+        emojis::get_by_shortcode(name).map(|emoji| emoji.as_str().to_string())
+    });
+    custom_to_shortcodes(&content)
+}
+
+//Calls lookup on every :name: and swaps it for whatever comes back
+fn replace_shortcodes(content: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut result = String::new();
+    let mut rest = content;
+
+    while let Some(open) = rest.find(':') {
+        let after = &rest[open + 1..];
+        let close = match after.find(':') {
+            Some(v) => v,
+            None => break,
+        };
+        let name = &after[..close];
+
+        //<:name:id> is already a custom emoji
+        let in_markup = rest[..open].ends_with('<') || rest[..open].ends_with("<a");
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '+' || c == '-');
+
+        let emoji = match valid && !in_markup {
+            true => lookup(name),
+            false => None,
+        };
+        match emoji {
+            Some(v) => {
+                result.push_str(&rest[..open]);
+                result.push_str(&v);
+                rest = &after[close + 1..];
+            }
+            //The closing colon might open the next one
+            None => {
+                result.push_str(&rest[..open + 1]);
+                rest = after;
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+//What the reaction endpoints need from a picked emoji
+pub fn reaction_emoji(candidate: &Candidate) -> ReactionEmoji {
+    let custom = candidate
+        .markup
+        .as_deref()
+        .and_then(|markup| markup.strip_prefix('<')?.strip_suffix('>'))
+        .and_then(parse_custom);
+
+    match custom {
+        Some((name, id)) => ReactionEmoji {
+            id: Some(id.to_string()),
+            name: name.to_string(),
+        },
+        None => ReactionEmoji {
+            id: None,
+            name: candidate.text.clone(),
+        },
+    }
+}
+
+//What's between the brackets of <:name:id> or <a:name:id>
+fn parse_custom(markup: &str) -> Option<(&str, &str)> {
+    let markup = markup.strip_prefix('a').unwrap_or(markup);
+    let parts: Vec<&str> = markup.split(':').collect();
+    match parts.as_slice() {
+        ["", name, id] if !name.is_empty() && id.chars().all(|c| c.is_ascii_digit()) => {
+            Some((name, id))
+        }
+        _ => None,
+    }
+}
+
+//<:name:id> and <a:name:id> to :name:
+fn custom_to_shortcodes(content: &str) -> String {
+    let mut result = String::new();
+    let mut rest = content;
+
+    while let Some(open) = rest.find('<') {
+        let inside = &rest[open + 1..];
+        let close = match inside.find('>') {
+            Some(v) => v,
+            None => break,
+        };
+
+        match parse_custom(&inside[..close]) {
+            Some((name, _)) => {
+                result.push_str(&rest[..open]);
+                result.push_str(&format!(":{}:", name));
+                rest = &inside[close + 1..];
+            }
+            None => {
+                result.push_str(&rest[..open + 1]);
+                rest = inside;
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::data::GuildEmoji;
+    use serde_json::json;
+
+    fn guild() -> Guild {
+        let mut guild = Guild::from_partial(&json!({ "id": "1", "name": "test" }));
+        guild.emojis.push(GuildEmoji {
+            id: "42".to_string(),
+            name: "party".to_string(),
+            animated: false,
+        });
+        guild.emojis.push(GuildEmoji {
+            id: "43".to_string(),
+            name: "spin".to_string(),
+            animated: true,
+        });
+        guild
+    }
+
+    #[test]
+    fn from_shortcodes_turns_names_into_emoji() {
+        assert_eq!(from_shortcodes("hi :smile:", None), "hi 😄");
+        assert_eq!(from_shortcodes(":+1: :-1:", None), "👍 👎");
+    }
+
+    #[test]
+    fn from_shortcodes_uses_the_guilds_custom_emoji() {
+        let guild = guild();
+        assert_eq!(from_shortcodes(":party:", Some(&guild)), "<:party:42>");
+        assert_eq!(from_shortcodes(":spin:", Some(&guild)), "<a:spin:43>");
+        assert_eq!(from_shortcodes(":party:", None), ":party:");
+    }
+
+    #[test]
+    fn from_shortcodes_leaves_unknown_and_broken_names() {
+        assert_eq!(from_shortcodes(":nope_not_real:", None), ":nope_not_real:");
+        assert_eq!(from_shortcodes("time 10:30:00", None), "time 10:30:00");
+        assert_eq!(from_shortcodes("just : colons :", None), "just : colons :");
+    }
+
+    #[test]
+    fn from_shortcodes_skips_code_and_links() {
+        assert_eq!(from_shortcodes("`:smile:` :smile:", None), "`:smile:` 😄");
+        assert_eq!(
+            from_shortcodes("```\na :smile: b\n``` :+1:", None),
+            "```\na :smile: b\n``` 👍"
+        );
+        assert_eq!(from_shortcodes("` :smile:", None), "` 😄");
+        assert_eq!(
+            from_shortcodes("https://example.com/:smile:/ :smile:", None),
+            "https://example.com/:smile:/ 😄"
+        );
+    }
+
+    #[test]
+    fn from_shortcodes_keeps_existing_custom_markup() {
+        let guild = guild();
+        assert_eq!(from_shortcodes("<:party:42>", Some(&guild)), "<:party:42>");
+        assert_eq!(from_shortcodes("<a:spin:43>", Some(&guild)), "<a:spin:43>");
+    }
+}
@@ -0,0 +1,101 @@
+//Popup for browsing to files to attach
+
+use std::path::PathBuf;
+
+use super::stateful_list::StatefulList;
+
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+pub struct FilePicker {
+    pub dir: PathBuf,
+    pub entries: StatefulList<FileEntry>,
+    //Files picked with space, sent together
+    pub marked: Vec<PathBuf>,
+}
+
+impl FilePicker {
+    //Starts wherever disrust was launched from
+    pub fn new() -> FilePicker {
+        let dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        let mut picker = FilePicker {
+            dir,
+            entries: StatefulList::from(Vec::new()),
+            marked: Vec::new(),
+        };
+        picker.load();
+        picker
+    }
+
+    //Folders first, then files, both by name
+    fn load(&mut self) {
+        let mut entries: Vec<FileEntry> = match std::fs::read_dir(&self.dir) {
+            Ok(v) => v
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let metadata = std::fs::metadata(entry.path()).ok()?;
+                    Some(FileEntry {
+                        path: entry.path(),
+                        name: entry.file_name().to_string_lossy().to_string(),
+                        is_dir: metadata.is_dir(),
+                        size: metadata.len(),
+                    })
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+
+        self.entries = StatefulList::from(entries);
+        if !self.entries.items.is_empty() {
+            self.entries.state.select(Some(0));
+        }
+    }
+
+    fn get_entry(&self) -> Option<FileEntry> {
+        let index = self.entries.state.selected()?;
+        self.entries.items.get(index).cloned()
+    }
+
+    //Goes into a folder, or gives back what to attach when it's a file
+    pub fn enter(&mut self) -> Option<Vec<PathBuf>> {
+        let entry = self.get_entry()?;
+        if entry.is_dir {
+            self.dir = entry.path;
+            self.load();
+            return None;
+        }
+
+        let mut files = self.marked.clone();
+        if !files.contains(&entry.path) {
+            files.push(entry.path);
+        }
+        Some(files)
+    }
+
+    pub fn parent(&mut self) {
+        if let Some(parent) = self.dir.parent() {
+            self.dir = parent.to_path_buf();
+            self.load();
+        }
+    }
+
+    pub fn toggle_mark(&mut self) {
+        let entry = match self.get_entry() {
+            Some(v) if !v.is_dir => v,
+            _ => return,
+        };
+
+        match self.marked.iter().position(|path| *path == entry.path) {
+            Some(i) => {
+                self.marked.remove(i);
+            }
+            None => self.marked.push(entry.path),
+        }
+    }
+}
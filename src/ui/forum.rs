@@ -0,0 +1,158 @@
+//Forum and media channels, shown as a list of posts instead of messages
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::api::data::{snowflake_newer, Channel, ForumTag};
+
+const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
+//What /post was given, tags already turned into ids
+pub struct NewPost {
+    pub title: String,
+    pub tags: Vec<String>,
+    pub body: String,
+}
+
+//"+tag +other Title | body", the body is optional
+pub fn parse_post(args: &str, available: &[ForumTag]) -> Result<NewPost, String> {
+    let (head, body) = match args.split_once('|') {
+        Some((head, body)) => (head, body.trim()),
+        None => (args, ""),
+    };
+
+    let mut tags = Vec::new();
+    let mut title = Vec::new();
+    for word in head.split_whitespace() {
+        match word.strip_prefix('+') {
+            Some(name) => {
+                let tag = available
+                    .iter()
+                    .find(|tag| tag.name.eq_ignore_ascii_case(name))
+                    .ok_or_else(|| format!("This forum has no {} tag", name))?;
+                tags.push(tag.id.clone());
+            }
+            None => title.push(word),
+        }
+    }
+
+    if title.is_empty() {
+        return Err("Usage: /post [+tag] <title> | <body>".to_string());
+    }
+    Ok(NewPost {
+        title: title.join(" "),
+        tags,
+        body: body.to_string(),
+    })
+}
+
+//Active and archived together, most recently active first
+pub fn merge_posts(active: Vec<Channel>, archived: &[Channel]) -> Vec<Channel> {
+    let mut posts = active;
+    for post in archived {
+        if !posts.contains(post) {
+            posts.push(post.clone());
+        }
+    }
+
+    posts.sort_by(|a, b| {
+        let (a, b) = (last_activity(a), last_activity(b));
+        match (snowflake_newer(a, b), snowflake_newer(b, a)) {
+            (true, _) => std::cmp::Ordering::Less,
+            (_, true) => std::cmp::Ordering::Greater,
+            _ => std::cmp::Ordering::Equal,
+        }
+    });
+    posts
+}
+
+//Posts nobody replied to yet only have their own id
+pub fn last_activity(post: &Channel) -> &str {
+    match post.last_message_id.is_empty() {
+        true => &post.id,
+        false => &post.last_message_id,
+    }
+}
+
+//"5m ago" from a snowflake, snowflakes carry their creation time
+pub fn ago(snowflake: &str) -> String {
+    let created = (snowflake.parse::<u64>().unwrap_or(0) >> 22) + DISCORD_EPOCH;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |v| v.as_millis() as u64);
+    let seconds = now.saturating_sub(created) / 1000;
+
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tags() -> Vec<ForumTag> {
+        vec![
+            ForumTag {
+                id: "1".to_string(),
+                name: "Bug".to_string(),
+                emoji: None,
+            },
+            ForumTag {
+                id: "2".to_string(),
+                name: "Help".to_string(),
+                emoji: Some("❓".to_string()),
+            },
+        ]
+    }
+
+    fn post(id: &str, last_message_id: &str) -> Channel {
+        Channel::from(&json!({ "id": id, "type": 11, "last_message_id": last_message_id }))
+    }
+
+    fn ids(posts: &[Channel]) -> Vec<&str> {
+        posts.iter().map(|post| post.id.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_post_with_tags_and_body() {
+        let post = parse_post("+bug +HELP It crashes | when I press q", &tags()).unwrap();
+        assert_eq!(post.title, "It crashes");
+        assert_eq!(post.tags, ["1", "2"]);
+        assert_eq!(post.body, "when I press q");
+    }
+
+    #[test]
+    fn parse_post_body_is_optional() {
+        let post = parse_post("Just a title", &tags()).unwrap();
+        assert_eq!(post.title, "Just a title");
+        assert!(post.tags.is_empty());
+        assert_eq!(post.body, "");
+    }
+
+    #[test]
+    fn parse_post_errors() {
+        assert_eq!(
+            parse_post("+nope title", &tags()).err().unwrap(),
+            "This forum has no nope tag"
+        );
+        assert!(parse_post("+bug | body", &tags()).is_err());
+        assert!(parse_post("", &tags()).is_err());
+    }
+
+    #[test]
+    fn merge_posts_sorts_by_last_activity() {
+        let active = vec![post("10", "20"), post("30", "")];
+        let archived = vec![post("5", "40"), post("10", "20")];
+        assert_eq!(ids(&merge_posts(active, &archived)), ["5", "30", "10"]);
+    }
+
+    #[test]
+    fn merge_posts_compares_snowflakes_as_numbers() {
+        let active = vec![post("9", ""), post("100", "")];
+        assert_eq!(ids(&merge_posts(active, &[])), ["100", "9"]);
+    }
+}
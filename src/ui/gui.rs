@@ -1,10 +1,11 @@
 //GUI = gooey
-use crossterm::event::{self, Event, KeyCode};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
     backend::Backend,
-    layout::{Alignment, Constraint, Direction, Layout},
+    layout::{Alignment, Constraint, Direction, Flex, Layout, Rect},
     style::{Color, Modifier, Style},
-    widgets::{Block, BorderType, Borders, List, ListItem, Paragraph},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, List, ListItem, ListState, Paragraph},
     Frame, Terminal,
 };
 use std::io;
@@ -15,8 +16,16 @@ use std::time::Instant;
 
 use crate::api::data::*;
 use crate::ui::channels::App;
+use crate::ui::clipboard::CopyTarget;
+use crate::ui::commands::COMMANDS;
+use crate::ui::emoji;
+use crate::ui::hints::HintAction;
+use crate::ui::stateful_list::{
+    file_item, forum_item, member_item, message_item, search_item, select_option_item,
+    sidebar_item, sidebar_name, voice_item, StatefulList,
+};
 use crate::ui::{
-    channels::DisplayMode::{ChannelMode, GuildMode},
+    channels::DisplayMode::{ChannelMode, FriendsMode, GuildMode},
     chat_box::{ChatBox, InputMode},
 };
 
@@ -34,6 +43,10 @@ pub fn run<B: Backend>(
             Ok(v) => app.react_to_gateway(&v),
             Err(_v) => {}
         }
+        //Whatever happened last loop may have changed the channel
+        cbox.follow_channel(app);
+        app.poll_transfers();
+        app.send_acks();
 
         //Draws the screen. Comment out when debugging
         terminal.draw(|f| ui(f, app, cbox))?;
@@ -45,28 +58,136 @@ pub fn run<B: Backend>(
         //CodeAesthetic would be upset
         //Have to use poll to avoid blocking
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                match cbox.input_mode {
-                    InputMode::Normal => match key.code {
-                        KeyCode::Char('q') => return Ok(()),
-                        KeyCode::Char('e') => cbox.toggle(),
-                        KeyCode::Left => app.unselect(),
-                        KeyCode::Down => app.next(),
-                        KeyCode::Up => app.previous(),
-                        KeyCode::Enter => app.enter_guild(),
-                        KeyCode::Esc => app.leave_guild(),
-                        _ => (),
-                    },
-                    InputMode::Editing => match key.code {
-                        KeyCode::Enter => cbox.send_message(app),
-                        KeyCode::Esc => cbox.toggle(),
-                        KeyCode::Char(c) => cbox.input.push(c),
-                        KeyCode::Backspace => {
-                            cbox.input.pop();
+            let key = match event::read()? {
+                Event::Key(v) => v,
+                Event::Paste(text) => {
+                    let popup_open = app.switcher.is_some()
+                        || app.emoji_picker.is_some()
+                        || app.file_picker.is_some()
+                        || app.search.is_some()
+                        || app.select_picker.is_some();
+                    if cbox.input_mode == InputMode::Editing && !popup_open {
+                        cbox.completion = None;
+                        cbox.insert_str(&text);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            //Ctrl-K works from anywhere
+            if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('k') {
+                app.open_switcher();
+                continue;
+            }
+            if app.switcher.is_some() {
+                switcher_input(app, key.code);
+                continue;
+            }
+            if app.emoji_picker.is_some() {
+                emoji_picker_input(app, cbox, key.code);
+                continue;
+            }
+            if app.file_picker.is_some() {
+                file_picker_input(app, cbox, key.code);
+                continue;
+            }
+            if app.copy_menu {
+                match key.code {
+                    KeyCode::Char('c') => app.copy(CopyTarget::Content),
+                    KeyCode::Char('l') => app.copy(CopyTarget::Link),
+                    KeyCode::Char('u') => app.copy(CopyTarget::AuthorId),
+                    KeyCode::Char('i') => app.copy(CopyTarget::ChannelId),
+                    _ => app.close_copy_menu(),
+                }
+                continue;
+            }
+            if app.search.is_some() {
+                search_input(app, key.code);
+                continue;
+            }
+            if app.select_picker.is_some() {
+                select_picker_input(app, key.code);
+                continue;
+            }
+            if app.component_focus.is_some() {
+                match key.code {
+                    KeyCode::Right | KeyCode::Tab | KeyCode::Char('l') => app.step_component(true),
+                    KeyCode::Left | KeyCode::BackTab | KeyCode::Char('h') => {
+                        app.step_component(false)
+                    }
+                    KeyCode::Enter | KeyCode::Char(' ') => app.press_component(),
+                    _ => app.close_components(),
+                }
+                continue;
+            }
+            if app.show_help {
+                app.show_help = false;
+                continue;
+            }
+            if app.hints.is_some() {
+                match key.code {
+                    KeyCode::Char(c) => app.hint_input(c),
+                    _ => app.close_hints(),
+                }
+                continue;
+            }
+            //Read it or lose it
+            app.status = None;
+
+            match cbox.input_mode {
+                InputMode::Normal => match key.code {
+                    KeyCode::Char('q') => {
+                        cbox.save_drafts();
+                        app.flush_acks();
+                        return Ok(());
+                    }
+                    KeyCode::Char('e') => cbox.toggle(),
+                    KeyCode::Char('f') => app.toggle_friends(),
+                    KeyCode::Char('m') => app.toggle_mute(),
+                    KeyCode::Char('u') => app.toggle_members(),
+                    KeyCode::Char('n') => app.toggle_usernames(),
+                    KeyCode::Left => app.unselect(),
+                    KeyCode::Down => app.next(),
+                    KeyCode::Up => app.previous(),
+                    KeyCode::Esc => app.leave_guild(),
+                    _ => match app.mode {
+                        FriendsMode => match key.code {
+                            KeyCode::Enter => app.open_dm(),
+                            KeyCode::Char('a') => app.accept_friend(),
+                            KeyCode::Char('d') => app.remove_friend(),
+                            _ => (),
+                        },
+                        ChannelMode => match key.code {
+                            KeyCode::Enter => app.enter_guild(),
+                            KeyCode::Char('k') => app.select_older_message(),
+                            KeyCode::Char('j') => app.select_newer_message(),
+                            KeyCode::Char('r') => app.react_to_selected(),
+                            KeyCode::Char('d') => app.download_selected(false),
+                            KeyCode::Char('o') => app.download_selected(true),
+                            KeyCode::Char('l') => app.start_hints(HintAction::Open),
+                            KeyCode::Char('y') => app.start_hints(HintAction::Copy),
+                            KeyCode::Char('c') => app.open_copy_menu(),
+                            KeyCode::Char('b') => app.focus_components(),
+                            KeyCode::Char('t') => app.open_thread(),
+                            _ => (),
+                        },
+                        GuildMode => {
+                            if key.code == KeyCode::Enter {
+                                app.enter_guild()
+                            }
                         }
-                        _ => (),
                     },
+                },
+                //Ctrl-G hands the draft to $EDITOR
+                InputMode::Editing
+                    if key.modifiers.contains(KeyModifiers::CONTROL)
+                        && key.code == KeyCode::Char('g') =>
+                {
+                    cbox.open_editor();
+                    //The editor drew over everything, redraw from scratch
+                    terminal.clear()?;
                 }
+                InputMode::Editing => editing_input(cbox, app, key),
             }
         }
         if last_tick.elapsed() >= tick_rate {
@@ -75,6 +196,161 @@ pub fn run<B: Backend>(
     }
 }
 
+//Line editing keys, mostly the readline ones
+fn editing_input(cbox: &mut ChatBox, app: &mut App, key: KeyEvent) {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    let newline = key
+        .modifiers
+        .intersects(KeyModifiers::SHIFT | KeyModifiers::ALT);
+    //Any other key accepts the completion
+    if !matches!(key.code, KeyCode::Tab | KeyCode::BackTab) {
+        cbox.completion = None;
+    }
+
+    match key.code {
+        KeyCode::Tab => cbox.complete(app, true),
+        KeyCode::BackTab => cbox.complete(app, false),
+        KeyCode::Enter if newline => cbox.insert_char('\n'),
+        KeyCode::Enter => cbox.send_message(app),
+        KeyCode::Esc => cbox.toggle(),
+        KeyCode::Char('w') if ctrl => cbox.delete_word(),
+        KeyCode::Char('u') if ctrl => cbox.delete_to_start(),
+        KeyCode::Char('a') if ctrl => cbox.home(),
+        KeyCode::Char('e') if ctrl => cbox.end(),
+        KeyCode::Char('o') if ctrl => app.open_emoji_picker(),
+        KeyCode::Char('x') if ctrl => cbox.attachments.clear(),
+        KeyCode::Char(c) => cbox.insert_char(c),
+        KeyCode::Backspace => cbox.backspace(),
+        KeyCode::Delete => cbox.delete(),
+        KeyCode::Left if ctrl => cbox.word_left(),
+        KeyCode::Right if ctrl => cbox.word_right(),
+        KeyCode::Left => cbox.move_left(),
+        KeyCode::Right => cbox.move_right(),
+        KeyCode::Home => cbox.home(),
+        KeyCode::End => cbox.end(),
+        KeyCode::Up => cbox.up(),
+        KeyCode::Down => cbox.down(),
+        _ => (),
+    }
+}
+
+//Keys go to the quick switcher while it's open
+fn switcher_input(app: &mut App, key: KeyCode) {
+    let switcher = match app.switcher.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    match key {
+        KeyCode::Esc => app.close_switcher(),
+        KeyCode::Enter => app.switch_to_selected(),
+        _ if move_selection(&mut switcher.results, key) => (),
+        _ if edit_query(&mut switcher.query, key) => app.update_switcher(),
+        _ => (),
+    }
+}
+
+//Up/Down in any popup list, false for other keys
+fn move_selection<T>(list: &mut StatefulList<T>, key: KeyCode) -> bool {
+    match key {
+        KeyCode::Down if !list.items.is_empty() => list.next(),
+        KeyCode::Up if !list.items.is_empty() => list.previous(),
+        KeyCode::Down | KeyCode::Up => (),
+        _ => return false,
+    }
+    true
+}
+
+//Typing into a popup's search box, false for other keys
+fn edit_query(query: &mut String, key: KeyCode) -> bool {
+    match key {
+        KeyCode::Backspace => {
+            query.pop();
+        }
+        KeyCode::Char(c) => query.push(c),
+        _ => return false,
+    }
+    true
+}
+
+//Up and down through the results, Enter opens the message's channel
+fn search_input(app: &mut App, key: KeyCode) {
+    let search = match app.search.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    match key {
+        KeyCode::Enter => app.jump_to_result(),
+        _ if move_selection(&mut search.results, key) => (),
+        _ => app.close_search(),
+    }
+}
+
+//Space marks options on menus that take several, Enter sends
+fn select_picker_input(app: &mut App, key: KeyCode) {
+    let picker = match app.select_picker.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    match key {
+        KeyCode::Enter => app.submit_select(),
+        KeyCode::Char(' ') => picker.toggle(),
+        KeyCode::Esc => app.close_select_picker(),
+        _ if move_selection(&mut picker.options, key) => (),
+        _ => (),
+    }
+}
+
+//Same as the switcher, Enter puts the emoji in the chat box
+fn emoji_picker_input(app: &mut App, cbox: &mut ChatBox, key: KeyCode) {
+    let picker = match app.emoji_picker.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    match key {
+        KeyCode::Esc => app.close_emoji_picker(),
+        KeyCode::Enter => {
+            let react_to = picker.react_to.clone();
+            match (picker.get_candidate(), react_to) {
+                (Some(candidate), Some(message)) => {
+                    app.toggle_reaction(&message, &emoji::reaction_emoji(&candidate))
+                }
+                (Some(candidate), None) => cbox.insert_emoji(candidate),
+                (None, _) => (),
+            }
+            app.close_emoji_picker();
+        }
+        _ if move_selection(&mut picker.results, key) => (),
+        _ if edit_query(&mut picker.query, key) => app.update_emoji_picker(),
+        _ => (),
+    }
+}
+
+//Enter goes into folders or attaches the file (and any marked ones)
+fn file_picker_input(app: &mut App, cbox: &mut ChatBox, key: KeyCode) {
+    let picker = match app.file_picker.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    match key {
+        KeyCode::Esc => app.close_file_picker(),
+        KeyCode::Enter => {
+            if let Some(files) = picker.enter() {
+                cbox.attach(files);
+                app.close_file_picker();
+            }
+        }
+        KeyCode::Char(' ') => picker.toggle_mark(),
+        KeyCode::Backspace | KeyCode::Left => picker.parent(),
+        _ if move_selection(&mut picker.entries, key) => (),
+        _ => (),
+    }
+}
+
 //Maybe make each block a function
 //Sets up how the ui looks like
 fn ui(f: &mut Frame, app: &mut App, cbox: &mut ChatBox) {
@@ -94,23 +370,51 @@ fn ui(f: &mut Frame, app: &mut App, cbox: &mut ChatBox) {
         .constraints([Constraint::Percentage(20), Constraint::Percentage(80)].as_ref())
         .split(f.area());
 
+    //The input box grows with what's typed, up to max_lines
+    let input_width = chunks[1].width.saturating_sub(2) as usize;
+    let (input_rows, (cursor_row, cursor_col)) = cbox.wrap(input_width);
+    let input_lines = (input_rows.len() as u16).clamp(1, app.config.input.max_lines.max(1));
+    cbox.update_scroll(cursor_row, input_lines as usize);
+
     let right_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(94), Constraint::Percentage(6)].as_ref())
+        .constraints([Constraint::Min(1), Constraint::Length(input_lines + 2)].as_ref())
         .split(chunks[1]);
 
     // Create the channels part
     let items = match app.mode {
-        GuildMode => List::from(app.guilds.clone()),
-        ChannelMode => List::from(app.channels.clone()),
+        GuildMode => List::new(app.guilds.items.iter().map(|guild| {
+            sidebar_item(
+                guild.name.clone(),
+                app.guild_unread(guild),
+                app.guild_muted(guild),
+            )
+        })),
+        ChannelMode => List::new(app.channels.items.iter().map(
+            |channel| match channel.is_voice() {
+                true => voice_item(
+                    sidebar_name(channel),
+                    app.channel_unread(channel),
+                    app.channel_muted(channel),
+                    &app.voice_members(channel),
+                ),
+                false => sidebar_item(
+                    sidebar_name(channel),
+                    app.channel_unread(channel),
+                    app.channel_muted(channel),
+                ),
+            },
+        )),
+        FriendsMode => List::from(app.friends.clone()),
+    };
+
+    let list_title = match app.mode {
+        FriendsMode => "Friends",
+        _ => "Guilds and Channels",
     };
 
     let items = items
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Guilds and Channels"),
-        )
+        .block(Block::default().borders(Borders::ALL).title(list_title))
         .highlight_style(
             Style::default()
                 .bg(Color::LightGreen)
@@ -128,6 +432,34 @@ fn ui(f: &mut Frame, app: &mut App, cbox: &mut ChatBox) {
         ChannelMode => {
             f.render_stateful_widget(items, chunks[0], &mut app.channels.state);
         }
+        FriendsMode => {
+            f.render_stateful_widget(items, chunks[0], &mut app.friends.state);
+        }
+    }
+
+    //Member list takes a slice off the right of the chat when it's open
+    let mut chat_area = right_chunks[0];
+    if let (true, ChannelMode) = (app.show_members, &app.mode) {
+        let chat_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(78), Constraint::Percentage(22)].as_ref())
+            .split(right_chunks[0]);
+        chat_area = chat_chunks[0];
+
+        let guild = app
+            .guilds
+            .items
+            .iter()
+            .find(|guild| guild.id == app.member_list_guild);
+        let members: Vec<ListItem> = app
+            .member_list
+            .iter()
+            .map(|item| member_item(item, guild))
+            .collect();
+        let members =
+            List::new(members).block(Block::default().borders(Borders::ALL).title("Members"));
+
+        f.render_widget(members, chat_chunks[1]);
     }
 
     // Could be better, a lot of cloning
@@ -135,27 +467,103 @@ fn ui(f: &mut Frame, app: &mut App, cbox: &mut ChatBox) {
     let chat_messages = app.get_messages();
 
     //If there are messages, use those, if there aren't advertise
-    if let Some(v) = chat_messages {
-        let chat = List::from(v);
-        let chat = chat.block(Block::default().borders(Borders::ALL).title(title));
+    if let Some(forum) = app.viewed_forum() {
+        let posts: Vec<ListItem> = app
+            .forum_posts
+            .items
+            .iter()
+            .map(|post| forum_item(post, &forum.available_tags))
+            .collect();
+        let title = format!(
+            "{} (j/k to pick, t opens, /post [+tag] <title> | <body> starts one)",
+            title
+        );
+        let list = List::new(posts)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, chat_area, &mut app.forum_posts.state);
+    } else if let Some(v) = chat_messages {
+        let guild_id = app.get_channel().guild_id;
+        let messages: Vec<ListItem> = v
+            .items
+            .iter()
+            .map(|message| {
+                let (author, color) = app.author_name(message, &guild_id);
+                let hints = match &app.hints {
+                    Some(hints) => hints.for_message(&message.id),
+                    None => Vec::new(),
+                };
+                let focused = match &app.component_focus {
+                    Some(focus) if focus.message_id == message.id => Some(focus.index),
+                    _ => None,
+                };
+                message_item(message, author, color, &hints, focused)
+            })
+            .collect();
+        let heights: Vec<usize> = messages.iter().map(|item| item.height()).collect();
+        let chat = List::new(messages)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+
+        let mut state = v.state.clone();
+        f.render_stateful_widget(chat, chat_area, &mut state);
 
-        f.render_widget(chat, right_chunks[0]);
+        //Hint mode only labels what's on screen
+        let start = state.offset().min(heights.len());
+        let mut space = chat_area.height.saturating_sub(2) as usize;
+        let mut end = start;
+        while end < heights.len() && heights[end] <= space {
+            space -= heights[end];
+            end += 1;
+        }
+        app.visible_messages = start..end;
+    } else if let FriendsMode = app.mode {
+        let help = vec![
+            ListItem::new("Enter: open a DM with the selected friend"),
+            ListItem::new("a: accept an incoming request"),
+            ListItem::new("d: remove friend / decline or cancel request / unblock"),
+            ListItem::new("f: back to guilds"),
+        ];
+        let chat = List::new(help).block(Block::default().borders(Borders::ALL).title(title));
+
+        f.render_widget(chat, chat_area);
     } else {
         let ad = vec![ListItem::new(
             "Check my other projects on https://github.com/DvorakDwarf",
         )];
         let chat = List::new(ad).block(Block::default().borders(Borders::ALL).title(title));
 
-        f.render_widget(chat, right_chunks[0]);
+        f.render_widget(chat, chat_area);
     }
 
     //The chat box is here
-    let input = Paragraph::new(cbox.input.as_str())
+    //Wrapped by us so the cursor lines up, scrolls down past max_lines
+    let input_text: Vec<Line> = input_rows.into_iter().map(Line::from).collect();
+    //Attached files on the left of the border, status on the right
+    let mut input_block = Block::default().borders(Borders::ALL).title("Input");
+    if !cbox.attachments.is_empty() {
+        let names: Vec<String> = cbox
+            .attachments
+            .iter()
+            .map(|path| {
+                path.file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        input_block = input_block.title(format!(" + {} (ctrl+x to drop) ", names.join(", ")));
+    }
+    if let Some(status) = app.get_status() {
+        input_block = input_block.title(Line::from(format!(" {} ", status)).right_aligned());
+    }
+    let input = Paragraph::new(input_text)
         .style(match cbox.input_mode {
             InputMode::Normal => Style::default(),
             InputMode::Editing => Style::default().fg(Color::Yellow),
         })
-        .block(Block::default().borders(Borders::ALL).title("Input"));
+        .scroll((cbox.scroll as u16, 0))
+        .block(input_block);
     f.render_widget(input, right_chunks[1]);
 
     match cbox.input_mode {
@@ -163,11 +571,309 @@ fn ui(f: &mut Frame, app: &mut App, cbox: &mut ChatBox) {
         InputMode::Editing => {
             //Set cursor as visible and move to right spot
             f.set_cursor_position((
-                // Put cursor past the end of the input text
-                right_chunks[1].x + cbox.input.len() as u16 + 1,
-                // Move one line down, from the border to the input line
-                right_chunks[1].y + 1,
+                // Columns up to the cursor on its row
+                right_chunks[1].x + cursor_col as u16 + 1,
+                // Rows down to the cursor, minus what's scrolled away, past the border
+                right_chunks[1].y + (cursor_row - cbox.scroll) as u16 + 1,
             ))
         }
     }
+
+    if cbox.completion.is_some() {
+        draw_completion(f, cbox, right_chunks[1]);
+    }
+    if app.switcher.is_some() {
+        draw_switcher(f, app);
+    }
+    if app.emoji_picker.is_some() {
+        draw_emoji_picker(f, app);
+    }
+    if app.file_picker.is_some() {
+        draw_file_picker(f, app);
+    }
+    if app.copy_menu {
+        draw_copy_menu(f);
+    }
+    if app.search.is_some() {
+        draw_search(f, app);
+    }
+    if app.select_picker.is_some() {
+        draw_select_picker(f, app);
+    }
+    if app.show_help {
+        draw_help(f);
+    }
+}
+
+fn draw_select_picker(f: &mut Frame, app: &mut App) {
+    let picker = match app.select_picker.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let items: Vec<ListItem> = picker
+        .options
+        .items
+        .iter()
+        .map(|option| {
+            let chosen = picker.chosen.contains(&option.value);
+            select_option_item(option, chosen, picker.multiple())
+        })
+        .collect();
+    let title = match picker.multiple() {
+        true => format!(
+            "{} (space marks, Enter sends, Esc cancels)",
+            picker.menu.placeholder
+        ),
+        false => format!("{} (Enter sends, Esc cancels)", picker.menu.placeholder),
+    };
+    let popup = Popup {
+        width: 50,
+        height: 50,
+        title,
+        query: None,
+    };
+    draw_popup(f, popup, items, &mut picker.options.state);
+}
+
+//Every slash command with its arguments, any key closes it
+fn draw_help(f: &mut Frame) {
+    let items: Vec<ListItem> = COMMANDS
+        .iter()
+        .map(|(name, usage, description)| {
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{} {}", name, usage),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ),
+                Span::raw(format!("  {}", description)),
+            ]))
+        })
+        .chain([
+            ListItem::new("Bots' commands work too, Tab after / lists them"),
+            ListItem::new("Start with // to send a message beginning with /"),
+        ])
+        .collect();
+
+    let [area] = Layout::horizontal([Constraint::Percentage(70)])
+        .flex(Flex::Center)
+        .areas(f.area());
+    let [area] = Layout::vertical([Constraint::Length(items.len() as u16 + 2)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    let help = List::new(items).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Commands (any key to close)"),
+    );
+    f.render_widget(Clear, area);
+    f.render_widget(help, area);
+}
+
+//Like the switcher, but the results are messages
+fn draw_search(f: &mut Frame, app: &mut App) {
+    let items: Vec<ListItem> = match app.search.as_ref() {
+        Some(search) => search
+            .results
+            .items
+            .iter()
+            .map(|message| search_item(message, &app.channel_label(&message.channel_id)))
+            .collect(),
+        None => return,
+    };
+    let search = app.search.as_mut().unwrap();
+
+    let title = format!(
+        "{} results for {} (Enter to jump, Esc to close)",
+        search.results.items.len(),
+        search.query
+    );
+    let popup = Popup {
+        width: 70,
+        height: 60,
+        title,
+        query: None,
+    };
+    draw_popup(f, popup, items, &mut search.results.state);
+}
+
+fn draw_copy_menu(f: &mut Frame) {
+    let options = vec![
+        ListItem::new("c: message text"),
+        ListItem::new("l: message link"),
+        ListItem::new("u: author's user id"),
+        ListItem::new("i: channel id"),
+    ];
+    let [area] = Layout::horizontal([Constraint::Length(30)])
+        .flex(Flex::Center)
+        .areas(f.area());
+    let [area] = Layout::vertical([Constraint::Length(options.len() as u16 + 2)])
+        .flex(Flex::Center)
+        .areas(area);
+
+    let menu = List::new(options).block(Block::default().borders(Borders::ALL).title("Copy"));
+    f.render_widget(Clear, area);
+    f.render_widget(menu, area);
+}
+
+fn draw_file_picker(f: &mut Frame, app: &mut App) {
+    let picker = match app.file_picker.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let items: Vec<ListItem> = picker
+        .entries
+        .items
+        .iter()
+        .map(|entry| file_item(entry, picker.marked.contains(&entry.path)))
+        .collect();
+    let title = format!(
+        "{} (space marks, backspace goes up, Esc cancels)",
+        picker.dir.display()
+    );
+    let popup = Popup {
+        width: 60,
+        height: 60,
+        title,
+        query: None,
+    };
+    draw_popup(f, popup, items, &mut picker.entries.state);
+}
+
+//Search on top, emoji under it, like the switcher but narrower
+fn draw_emoji_picker(f: &mut Frame, app: &mut App) {
+    let picker = match app.emoji_picker.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let items: Vec<ListItem> = picker
+        .results
+        .items
+        .iter()
+        .cloned()
+        .map(ListItem::from)
+        .collect();
+    let popup = Popup {
+        width: 40,
+        height: 50,
+        title: "Emoji (Esc to cancel)".to_string(),
+        query: Some(&picker.query),
+    };
+    draw_popup(f, popup, items, &mut picker.results.state);
+}
+
+//Candidates stacked right above the input box
+fn draw_completion(f: &mut Frame, cbox: &ChatBox, input_area: Rect) {
+    let completion = match &cbox.completion {
+        Some(v) => v,
+        None => return,
+    };
+
+    let height = (completion.candidates.len() as u16).min(8) + 2;
+    let area = Rect {
+        x: input_area.x,
+        y: input_area.y.saturating_sub(height),
+        width: input_area.width.min(50),
+        height: height.min(input_area.y),
+    };
+
+    let items: Vec<ListItem> = completion
+        .candidates
+        .iter()
+        .map(|candidate| ListItem::new(candidate.label.as_str()))
+        .collect();
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::ALL).title("Tab to cycle"))
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+        );
+    let mut state = ListState::default();
+    state.select(Some(completion.index));
+
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(list, area, &mut state);
+}
+
+//Popup in the middle of the screen, search on top and results under it
+fn draw_switcher(f: &mut Frame, app: &mut App) {
+    let switcher = match app.switcher.as_mut() {
+        Some(v) => v,
+        None => return,
+    };
+
+    let items: Vec<ListItem> = switcher
+        .results
+        .items
+        .iter()
+        .cloned()
+        .map(ListItem::from)
+        .collect();
+    let popup = Popup {
+        width: 60,
+        height: 50,
+        title: "Jump to (Esc to cancel)".to_string(),
+        query: Some(&switcher.query),
+    };
+    draw_popup(f, popup, items, &mut switcher.results.state);
+}
+
+//Size in percent of the screen, the title goes on the query box when there is one
+struct Popup<'a> {
+    width: u16,
+    height: u16,
+    title: String,
+    //Shows what's been typed above the list, with the cursor after it
+    query: Option<&'a str>,
+}
+
+//Every picker is a centered list highlighted like the sidebar
+fn draw_popup(f: &mut Frame, popup: Popup, items: Vec<ListItem>, state: &mut ListState) {
+    let area = centered_rect(f.area(), popup.width, popup.height);
+    f.render_widget(Clear, area);
+
+    let (list_area, list_block) = match popup.query {
+        Some(query) => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(3), Constraint::Min(1)].as_ref())
+                .split(area);
+            let query_box = Paragraph::new(query)
+                .block(Block::default().borders(Borders::ALL).title(popup.title));
+            f.render_widget(query_box, chunks[0]);
+            f.set_cursor_position((
+                chunks[0].x + query.chars().count() as u16 + 1,
+                chunks[0].y + 1,
+            ));
+            (chunks[1], Block::default().borders(Borders::ALL))
+        }
+        None => (
+            area,
+            Block::default().borders(Borders::ALL).title(popup.title),
+        ),
+    };
+
+    let list = List::new(items)
+        .block(list_block)
+        .highlight_style(
+            Style::default()
+                .bg(Color::LightGreen)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol(">> ");
+    f.render_stateful_widget(list, list_area, state);
+}
+
+fn centered_rect(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [area] = Layout::vertical([Constraint::Percentage(percent_y)])
+        .flex(Flex::Center)
+        .areas(area);
+    area
 }
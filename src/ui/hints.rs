@@ -0,0 +1,170 @@
+//Hint mode, every link on screen gets a short label to type, like vimium/urlview
+
+use crate::api::data::Msg;
+
+//Home row first, they're the easiest to type
+const LABEL_CHARS: &str = "asdfghjklqwertyuiopzxcvbnm";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HintAction {
+    Open,
+    Copy,
+}
+
+#[derive(Debug, Clone)]
+pub struct Hint {
+    pub label: String,
+    pub url: String,
+    pub message_id: String,
+}
+
+pub struct HintMode {
+    pub hints: Vec<Hint>,
+    pub action: HintAction,
+    //What's been typed of a label so far
+    pub typed: String,
+}
+
+impl HintMode {
+    //None if there are no links to label
+    pub fn new(messages: &[Msg], action: HintAction) -> Option<HintMode> {
+        let mut found: Vec<(String, String)> = Vec::new();
+        for message in messages {
+            let urls = find_urls(&message.content)
+                .into_iter()
+                .map(|url| url.to_string())
+                .chain(message.attachments.iter().map(|v| v.url.clone()));
+            for url in urls {
+                //Same link twice in a message only needs one label
+                let pair = (message.id.clone(), url);
+                if !found.contains(&pair) {
+                    found.push(pair);
+                }
+            }
+        }
+        if found.is_empty() {
+            return None;
+        }
+
+        let labels = labels(found.len());
+        let hints = found
+            .into_iter()
+            .zip(labels)
+            .map(|((message_id, url), label)| Hint {
+                label,
+                url,
+                message_id,
+            })
+            .collect();
+
+        Some(HintMode {
+            hints,
+            action,
+            typed: String::new(),
+        })
+    }
+
+    //The hint once a whole label is typed
+    pub fn get_match(&self) -> Option<&Hint> {
+        self.hints.iter().find(|hint| hint.label == self.typed)
+    }
+
+    //Whether any label still starts with what was typed
+    pub fn any_left(&self) -> bool {
+        self.hints
+            .iter()
+            .any(|hint| hint.label.starts_with(&self.typed))
+    }
+
+    //url -> label for one message, for drawing
+    pub fn for_message(&self, message_id: &str) -> Vec<(String, String)> {
+        self.hints
+            .iter()
+            .filter(|hint| hint.message_id == message_id)
+            .map(|hint| (hint.url.clone(), hint.label.clone()))
+            .collect()
+    }
+}
+
+//All the same length so no label is the start of another
+fn labels(count: usize) -> Vec<String> {
+    let chars: Vec<char> = LABEL_CHARS.chars().collect();
+    let mut length = 1;
+    while chars.len().pow(length) < count {
+        length += 1;
+    }
+
+    (0..count)
+        .map(|mut i| {
+            let mut label = String::new();
+            for _ in 0..length {
+                label.insert(0, chars[i % chars.len()]);
+                i /= chars.len();
+            }
+            label
+        })
+        .collect()
+}
+
+//http(s) links, without the <> discord uses to hide embeds or trailing punctuation
+pub fn find_urls(text: &str) -> Vec<&str> {
+    let mut urls = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = ["https://", "http://"]
+        .iter()
+        .filter_map(|scheme| rest.find(scheme))
+        .min()
+    {
+        let candidate = &rest[start..];
+        let end = candidate
+            .find(|c: char| c.is_whitespace() || c == '<' || c == '>')
+            .unwrap_or(candidate.len());
+        let url = candidate[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '\'', '"']);
+
+        if url.len() > "https://".len() {
+            urls.push(url);
+        }
+        rest = &candidate[end..];
+    }
+
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_unique_and_the_same_length() {
+        assert_eq!(labels(3), ["a", "s", "d"]);
+
+        let many = labels(LABEL_CHARS.len() + 1);
+        assert!(many.iter().all(|label| label.len() == 2));
+        let unique: std::collections::HashSet<&String> = many.iter().collect();
+        assert_eq!(unique.len(), many.len());
+
+        assert!(labels(0).is_empty());
+    }
+
+    #[test]
+    fn find_urls_in_text() {
+        assert_eq!(
+            find_urls("see https://a.com/x and http://b.org, thanks"),
+            ["https://a.com/x", "http://b.org"]
+        );
+        assert!(find_urls("no links here").is_empty());
+    }
+
+    #[test]
+    fn find_urls_strips_wrapping() {
+        assert_eq!(find_urls("<https://a.com/x>"), ["https://a.com/x"]);
+        assert_eq!(find_urls("(https://a.com/x)."), ["https://a.com/x"]);
+        assert_eq!(find_urls("\"https://a.com/?q=1\"!"), ["https://a.com/?q=1"]);
+    }
+
+    #[test]
+    fn find_urls_skips_bare_schemes() {
+        assert!(find_urls("https:// http://").is_empty());
+    }
+}
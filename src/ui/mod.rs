@@ -1,6 +1,22 @@
 //establishes tree, don't look too hard
 pub mod channels;
 pub mod chat_box;
+pub mod clipboard;
+pub mod commands;
+pub mod completion;
+pub mod components;
+pub mod download;
+pub mod editor;
+pub mod emoji;
+pub mod file_picker;
+pub mod forum;
 pub mod gui;
+pub mod hints;
+pub mod notifications;
+pub mod opener;
+pub mod search;
 pub mod stateful_list;
+pub mod switcher;
+pub mod transfer;
+pub mod upload;
 
@@ -0,0 +1,111 @@
+//Desktop notifications with terminal fallbacks
+//D-Bus goes through gdbus so we don't need a whole D-Bus crate for one call
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{NotificationConfig, NotificationMethod};
+
+pub fn notify(config: &NotificationConfig, title: &str, body: &str) {
+    match config.method {
+        NotificationMethod::Off => (),
+        NotificationMethod::Bell => bell(),
+        NotificationMethod::Osc => osc(title, body),
+        NotificationMethod::Dbus => {
+            dbus(title, body);
+        }
+        NotificationMethod::Auto => {
+            if !dbus(title, body) {
+                osc(title, body);
+            }
+        }
+    }
+}
+
+//org.freedesktop.Notifications.Notify, returns false if it couldn't be sent
+fn dbus(title: &str, body: &str) -> bool {
+    if std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_none() {
+        return false;
+    }
+
+    let child = Command::new("gdbus")
+        .args([
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.Notifications",
+            "--object-path",
+            "/org/freedesktop/Notifications",
+            "--method",
+            "org.freedesktop.Notifications.Notify",
+            "'Disrust'",
+            "0",
+            "''",
+            &gvariant_string(title),
+            &gvariant_string(body),
+            "[]",
+            "{}",
+            "-1",
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    let mut child = match child {
+        Ok(v) => v,
+        Err(_) => return false,
+    };
+
+    //gdbus exits non-zero when nothing owns the notification service,
+    //wait a little for that so auto can still fall back to the terminal
+    let deadline = Instant::now() + Duration::from_millis(200);
+    while Instant::now() < deadline {
+        match child.try_wait() {
+            Ok(Some(status)) => return status.success(),
+            Ok(None) => thread::sleep(Duration::from_millis(10)),
+            Err(_) => return false,
+        }
+    }
+
+    //Still going, assume the daemon is just slow and reap it without blocking the ui
+    thread::spawn(move || child.wait());
+    true
+}
+
+//gdbus parses its arguments as GVariant text
+fn gvariant_string(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('\'', "\\'");
+    format!("'{}'", escaped)
+}
+
+//OSC 9 is understood by iTerm2, WezTerm, Windows Terminal and kitty
+//OSC 777 by foot, urxvt, ghostty and most vte terminals
+fn osc(title: &str, body: &str) {
+    //Control characters would end the escape early
+    let clean = |text: &str| text.replace(|c: char| c.is_control() || c == ';', " ");
+
+    let osc9 = matches!(
+        std::env::var("TERM_PROGRAM").as_deref(),
+        Ok("iTerm.app") | Ok("WezTerm")
+    ) || std::env::var_os("WT_SESSION").is_some()
+        || std::env::var_os("KITTY_WINDOW_ID").is_some();
+
+    let escape = match osc9 {
+        true => format!("\x1b]9;{}: {}\x07", clean(title), clean(body)),
+        false => format!("\x1b]777;notify;{};{}\x07", clean(title), clean(body)),
+    };
+
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(escape.as_bytes());
+    let _ = stdout.flush();
+    bell();
+}
+
+fn bell() {
+    let mut stdout = std::io::stdout();
+    let _ = stdout.write_all(b"\x07");
+    let _ = stdout.flush();
+}
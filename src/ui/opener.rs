@@ -0,0 +1,30 @@
+//Hands files and links to other programs without waiting on them
+
+use std::ffi::OsStr;
+use std::process::{Command, Stdio};
+use std::thread;
+
+//command gets target as its last argument, the system's default opener when None
+pub fn open_with(command: Option<&str>, target: impl AsRef<OsStr>) {
+    let default = match cfg!(target_os = "macos") {
+        true => "open",
+        false => "xdg-open",
+    };
+    let command = command.unwrap_or(default);
+
+    //Through sh so the command can have arguments of its own
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", command))
+        .arg("sh")
+        .arg(target)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    if let Ok(mut v) = child {
+        //Reap it without blocking the ui
+        thread::spawn(move || v.wait());
+    }
+}
@@ -0,0 +1,29 @@
+//Results of /search, a popup like the switcher that jumps to the message's channel
+
+use crate::api::data::Msg;
+
+use super::stateful_list::StatefulList;
+
+pub struct SearchResults {
+    pub query: String,
+    pub results: StatefulList<Msg>,
+}
+
+impl SearchResults {
+    pub fn new(query: &str, results: Vec<Msg>) -> SearchResults {
+        let mut results = StatefulList::from(results);
+        if !results.items.is_empty() {
+            results.state.select(Some(0));
+        }
+
+        SearchResults {
+            query: query.to_string(),
+            results,
+        }
+    }
+
+    pub fn get_message(&self) -> Option<Msg> {
+        let index = self.results.state.selected()?;
+        self.results.items.get(index).cloned()
+    }
+}
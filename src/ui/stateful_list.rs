@@ -1,10 +1,20 @@
 use ratatui::{
-    style::{Color, Style},
-    text::Span,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
     widgets::{List, ListItem, ListState},
 };
 
-use crate::api::data::{Channel, Guild, Msg};
+use crate::api::data::{
+    Channel, Component, ForumTag, Guild, MemberListItem, Msg, Relationship, RelationshipKind,
+    SelectOption, VoiceState,
+};
+use crate::ui::channels::ReadStatus;
+use crate::ui::completion::Candidate;
+use crate::ui::emoji;
+use crate::ui::file_picker::FileEntry;
+use crate::ui::forum;
+use crate::ui::switcher::SwitchTarget;
+use crate::ui::transfer::human_size;
 
 #[derive(Debug, Clone)]
 pub struct StatefulList<Element> {
@@ -66,23 +76,436 @@ impl<Element> From<Vec<Element>> for StatefulList<Element> {
 
 impl From<Msg> for ListItem<'_> {
     fn from(value: Msg) -> Self {
-        let name = value.user.name;
-        let content = value.content;
-        let combo = format!("{}: {}", name, content);
-        ListItem::new(combo)
+        let name = value.user.name.clone();
+        message_item(&value, name, None, &[], None)
+    }
+}
+
+//Discord colors are 0xRRGGBB
+fn role_style(color: Option<u32>) -> Style {
+    match color {
+        Some(color) => {
+            let [_, r, g, b] = color.to_be_bytes();
+            Style::default().fg(Color::Rgb(r, g, b))
+        }
+        None => Style::default(),
+    }
+}
+
+//Author in their role color, then the message
+//Attachments and reactions go on lines under it, our reactions highlighted
+//hints are (url, label) pairs from hint mode, the label goes in front of its link
+//focused is the index of the button or menu with keyboard focus, if it's this message
+pub fn message_item<'a>(
+    message: &Msg,
+    author: String,
+    color: Option<u32>,
+    hints: &[(String, String)],
+    focused: Option<usize>,
+) -> ListItem<'a> {
+    let author_style = role_style(color);
+    let dim = Style::default().fg(Color::DarkGray);
+
+    let mut lines = Vec::new();
+    //Bot replies say which command they answer
+    if let Some(interaction) = &message.interaction {
+        lines.push(Line::styled(
+            format!(
+                "╭ {} used /{}",
+                interaction.user.display_name(),
+                interaction.command
+            ),
+            dim,
+        ));
+    }
+
+    let mut spans = vec![Span::styled(author, author_style), Span::raw(": ")];
+    match message.is_loading() {
+        true => spans.push(Span::styled("is thinking...", dim)),
+        false => spans.extend(content_spans(emoji::display(&message.content), hints)),
+    }
+    if message.is_published() {
+        spans.push(Span::styled(" (published)", dim));
+    }
+    lines.push(Line::from(spans));
+    if message.is_ephemeral() {
+        lines.push(Line::styled(
+            "  Only you can see this",
+            dim.add_modifier(Modifier::ITALIC),
+        ));
+    }
+
+    for attachment in &message.attachments {
+        let mut spans = vec![Span::raw("  ")];
+        if let Some((_, label)) = hints.iter().find(|(url, _)| *url == attachment.url) {
+            spans.push(hint_label(label));
+        }
+        spans.push(Span::styled(
+            format!("[{} {}]", attachment.filename, human_size(attachment.size)),
+            Style::default().fg(Color::Cyan),
+        ));
+        lines.push(Line::from(spans));
+    }
+    if let Some(thread) = &message.thread {
+        let count = thread.thread.as_ref().map_or(0, |info| info.message_count);
+        let replies = match count {
+            1 => "1 reply".to_string(),
+            count => format!("{} replies", count),
+        };
+        lines.push(Line::styled(
+            format!("  ↳ {} · {} (t opens it)", thread.name, replies),
+            Style::default().fg(Color::LightBlue),
+        ));
+    }
+    let mut index = 0;
+    for row in &message.components {
+        let mut spans = vec![Span::raw("  ")];
+        for component in row {
+            let mut span = component_span(component);
+            if focused == Some(index) {
+                span = span.patch_style(
+                    Style::default().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+                );
+            }
+            spans.push(span);
+            spans.push(Span::raw(" "));
+            index += 1;
+        }
+        lines.push(Line::from(spans));
+    }
+    if message.reactions.is_empty() {
+        return ListItem::new(lines);
+    }
+
+    let mut reactions = vec![Span::raw("  ")];
+    for reaction in &message.reactions {
+        let style = match reaction.me {
+            true => Style::default().fg(Color::Black).bg(Color::LightBlue),
+            false => Style::default().fg(Color::DarkGray),
+        };
+        let text = format!("{} {}", reaction.emoji.display(), reaction.count);
+        reactions.push(Span::styled(text, style));
+        reactions.push(Span::raw(" "));
+    }
+    lines.push(Line::from(reactions));
+    ListItem::new(lines)
+}
+
+//Splits the content around hinted links so each gets its label
+fn content_spans<'a>(content: String, hints: &[(String, String)]) -> Vec<Span<'a>> {
+    let mut spans = Vec::new();
+    let mut rest = content.as_str();
+
+    loop {
+        let next = hints
+            .iter()
+            .filter_map(|(url, label)| Some((rest.find(url.as_str())?, url, label)))
+            .min_by_key(|(i, _, _)| *i);
+        let (i, url, label) = match next {
+            Some(v) => v,
+            None => break,
+        };
+
+        spans.push(Span::raw(rest[..i].to_string()));
+        spans.push(hint_label(label));
+        spans.push(Span::styled(
+            url.clone(),
+            Style::default().add_modifier(Modifier::UNDERLINED),
+        ));
+        rest = &rest[i + url.len()..];
+    }
+
+    spans.push(Span::raw(rest.to_string()));
+    spans
+}
+
+//Buttons colored like discord's, menus show what's picked by default
+fn component_span<'a>(component: &Component) -> Span<'a> {
+    let (text, style) = match component {
+        Component::Button(button) => match button.style {
+            5 => (
+                format!(" {} ↗ ", button.text()),
+                Style::default().add_modifier(Modifier::UNDERLINED),
+            ),
+            style => {
+                let color = match style {
+                    1 => Color::Blue,
+                    3 => Color::Green,
+                    4 => Color::Red,
+                    _ => Color::Gray,
+                };
+                (
+                    format!(" {} ", button.text()),
+                    Style::default().fg(Color::Black).bg(color),
+                )
+            }
+        },
+        Component::Select(menu) => {
+            let picked: Vec<&str> = menu
+                .options
+                .iter()
+                .filter(|option| option.default)
+                .map(|option| option.label.as_str())
+                .collect();
+            let text = match picked.is_empty() {
+                true => menu.placeholder.clone(),
+                false => picked.join(", "),
+            };
+            (
+                format!("[{} ▾]", text),
+                Style::default().fg(Color::White).bg(Color::DarkGray),
+            )
+        }
+    };
+
+    match component.disabled() {
+        true => Span::styled(text, Style::default().fg(Color::DarkGray)),
+        false => Span::styled(text, style),
+    }
+}
+
+//Marks show which options of a menu are picked
+pub fn select_option_item<'a>(option: &SelectOption, chosen: bool, multiple: bool) -> ListItem<'a> {
+    let mark = match (multiple, chosen) {
+        (false, _) => "",
+        (true, true) => "[x] ",
+        (true, false) => "[ ] ",
+    };
+
+    let mut spans = vec![Span::raw(format!("{}{}", mark, option.label))];
+    if let Some(description) = &option.description {
+        spans.push(Span::styled(
+            format!("  {}", description),
+            Style::default().fg(Color::DarkGray),
+        ));
     }
+    ListItem::new(Line::from(spans))
+}
+
+fn hint_label<'a>(label: &str) -> Span<'a> {
+    Span::styled(
+        format!("[{}]", label),
+        Style::default()
+            .fg(Color::Black)
+            .bg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    )
 }
 
 impl From<Guild> for ListItem<'_> {
     fn from(value: Guild) -> Self {
-        let text = value.name.clone();
-        ListItem::new(text).style(Style::default().fg(Color::Black).bg(Color::White))
+        sidebar_item(value.name, ReadStatus::Read, false)
     }
 }
 
 impl From<Channel> for ListItem<'_> {
     fn from(value: Channel) -> Self {
-        let text = value.name.clone();
-        ListItem::new(text).style(Style::default().fg(Color::Black).bg(Color::White))
+        sidebar_item(sidebar_name(&value), ReadStatus::Read, false)
+    }
+}
+
+//Title and tags, then how busy it is
+pub fn forum_item<'a>(post: &Channel, tags: &[ForumTag]) -> ListItem<'a> {
+    let mut title = vec![Span::styled(
+        post.name.clone(),
+        Style::default().add_modifier(Modifier::BOLD),
+    )];
+    for tag in tags
+        .iter()
+        .filter(|tag| post.applied_tags.contains(&tag.id))
+    {
+        title.push(Span::raw(" "));
+        title.push(Span::styled(
+            format!("[{}]", tag.display()),
+            Style::default().fg(Color::LightBlue),
+        ));
+    }
+
+    let info = post.thread.as_ref();
+    let replies = info.map_or(0, |info| info.message_count);
+    let mut details = format!(
+        "  {} replies · {}",
+        replies,
+        forum::ago(forum::last_activity(post))
+    );
+    if info.is_some_and(|info| info.archived) {
+        details.push_str(" · archived");
+    }
+
+    ListItem::new(vec![
+        Line::from(title),
+        Line::styled(details, Style::default().fg(Color::DarkGray)),
+    ])
+}
+
+//Threads sit indented under their channel
+//Announcement, voice and stage channels get an icon
+pub fn sidebar_name(channel: &Channel) -> String {
+    let icon = match channel.channel_type.as_str() {
+        "5" => "📢 ",
+        "2" => "🔊 ",
+        "13" => "🎤 ",
+        _ => "",
+    };
+    match channel.is_thread() {
+        true => format!("  ↳ {}", channel.name),
+        false => format!("{}{}", icon, channel.name),
+    }
+}
+
+//Guild or channel entry, bold if unread and with a ping counter if mentioned
+//Muted ones are greyed out
+pub fn sidebar_item<'a>(name: String, unread: ReadStatus, muted: bool) -> ListItem<'a> {
+    ListItem::new(sidebar_line(name, unread)).style(sidebar_style(unread, muted))
+}
+
+//Voice channel with whoever is connected listed under it
+pub fn voice_item<'a>(
+    name: String,
+    unread: ReadStatus,
+    muted: bool,
+    members: &[(String, VoiceState)],
+) -> ListItem<'a> {
+    let mut lines = vec![sidebar_line(name, unread)];
+    for (name, state) in members {
+        let mut spans = vec![Span::raw(format!("    {}", name))];
+        let indicators = state.indicators();
+        if !indicators.is_empty() {
+            spans.push(Span::raw(format!(" {}", indicators)));
+        }
+        if state.streaming {
+            let live = Style::default()
+                .fg(Color::White)
+                .bg(Color::Red)
+                .add_modifier(Modifier::BOLD);
+            spans.push(Span::raw(" "));
+            spans.push(Span::styled("LIVE", live));
+        }
+        lines.push(Line::from(spans).style(Style::default().remove_modifier(Modifier::BOLD)));
+    }
+
+    ListItem::new(lines).style(sidebar_style(unread, muted))
+}
+
+fn sidebar_style(unread: ReadStatus, muted: bool) -> Style {
+    let mut style = Style::default().fg(Color::Black).bg(Color::White);
+    if muted {
+        style = style.fg(Color::DarkGray);
+    }
+    if unread != ReadStatus::Read {
+        style = style.add_modifier(Modifier::BOLD);
+    }
+    style
+}
+
+fn sidebar_line<'a>(name: String, unread: ReadStatus) -> Line<'a> {
+    let mut spans = vec![Span::raw(name)];
+    if let ReadStatus::Mentioned(count) = unread {
+        let badge = Style::default()
+            .fg(Color::White)
+            .bg(Color::Red)
+            .add_modifier(Modifier::BOLD);
+        spans.push(Span::raw(" "));
+        spans.push(Span::styled(format!(" {} ", count), badge));
+    }
+    Line::from(spans)
+}
+
+fn status_color(status: &str) -> Color {
+    match status {
+        "online" => Color::Green,
+        "idle" => Color::Yellow,
+        "dnd" => Color::Red,
+        _ => Color::DarkGray,
+    }
+}
+
+//Group headers get the role name, members a presence dot
+pub fn member_item<'a>(item: &MemberListItem, guild: Option<&Guild>) -> ListItem<'a> {
+    match item {
+        MemberListItem::Group { id, count } => {
+            let name = match id.as_str() {
+                "online" => "Online".to_string(),
+                "offline" => "Offline".to_string(),
+                role_id => match guild.and_then(|guild| guild.get_role(role_id)) {
+                    Some(role) => role.name.clone(),
+                    None => "Unknown role".to_string(),
+                },
+            };
+
+            let header = format!("{} — {}", name.to_uppercase(), count);
+            ListItem::new(header).style(Style::default().add_modifier(Modifier::BOLD))
+        }
+        MemberListItem::Member(member) => {
+            let name = member.display_name().to_string();
+            let name_style = role_style(guild.and_then(|guild| member.color(guild)));
+
+            let dot = Span::styled("● ", Style::default().fg(status_color(&member.status)));
+            ListItem::new(Line::from(vec![dot, Span::styled(name, name_style)]))
+        }
+    }
+}
+
+impl From<Relationship> for ListItem<'_> {
+    fn from(value: Relationship) -> Self {
+        //Presence only matters for actual friends
+        let text = match value.kind {
+            RelationshipKind::Friend => format!("● {} ({})", value.user.name, value.status),
+            kind => format!("{} [{}]", value.user.name, kind.label()),
+        };
+        let fg = match value.kind {
+            RelationshipKind::Friend => status_color(&value.status),
+            _ => Color::Black,
+        };
+
+        ListItem::new(text).style(Style::default().fg(fg).bg(Color::White))
     }
 }
+
+impl From<SwitchTarget> for ListItem<'_> {
+    fn from(value: SwitchTarget) -> Self {
+        ListItem::new(value.label)
+    }
+}
+
+//Folders end in a slash, files show their size, marked files get a star
+pub fn file_item<'a>(entry: &FileEntry, marked: bool) -> ListItem<'a> {
+    let mark = match marked {
+        true => "* ",
+        false => "  ",
+    };
+
+    match entry.is_dir {
+        true => ListItem::new(format!("{}{}/", mark, entry.name))
+            .style(Style::default().add_modifier(Modifier::BOLD)),
+        false => ListItem::new(Line::from(vec![
+            Span::raw(format!("{}{}", mark, entry.name)),
+            Span::styled(
+                format!("  {}", human_size(entry.size)),
+                Style::default().fg(Color::DarkGray),
+            ),
+        ])),
+    }
+}
+
+impl From<Candidate> for ListItem<'_> {
+    fn from(value: Candidate) -> Self {
+        ListItem::new(value.label)
+    }
+}
+
+//One line per hit, where it was said first
+pub fn search_item<'a>(message: &Msg, channel: &str) -> ListItem<'a> {
+    let content = emoji::display(&message.content).replace('\n', " ");
+    ListItem::new(Line::from(vec![
+        Span::styled(
+            format!("{} ", channel),
+            Style::default().fg(Color::DarkGray),
+        ),
+        Span::styled(
+            format!("{}: ", message.user.display_name()),
+            Style::default().add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(content),
+    ]))
+}
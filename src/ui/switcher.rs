@@ -0,0 +1,176 @@
+//Ctrl-K popup for jumping anywhere by typing part of its name
+
+use crate::api::data::Channel;
+
+use super::stateful_list::StatefulList;
+
+//A guild on its own, a channel in a guild, or a DM (no guild)
+#[derive(Debug, Clone)]
+pub struct SwitchTarget {
+    pub label: String,
+    pub guild_id: Option<String>,
+    pub channel: Option<Channel>,
+}
+
+pub struct QuickSwitcher {
+    pub query: String,
+    pub results: StatefulList<SwitchTarget>,
+}
+
+impl QuickSwitcher {
+    pub fn new() -> QuickSwitcher {
+        QuickSwitcher {
+            query: String::new(),
+            results: StatefulList::from(Vec::new()),
+        }
+    }
+
+    //Reranks the targets against the query
+    //recent is most recently visited first
+    pub fn update(&mut self, targets: Vec<SwitchTarget>, recent: &[Channel]) {
+        let mut scored: Vec<(i64, SwitchTarget)> = targets
+            .into_iter()
+            .filter_map(|target| {
+                let score = fuzzy_score(&self.query, &target.label)?;
+                Some((score + recency_bonus(&target, recent), target))
+            })
+            .collect();
+        //Stable, so equal scores keep sidebar order
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        let results: Vec<SwitchTarget> = scored
+            .into_iter()
+            .take(50)
+            .map(|(_, target)| target)
+            .collect();
+        self.results = StatefulList::from(results);
+        if !self.results.items.is_empty() {
+            self.results.state.select(Some(0));
+        }
+    }
+
+    pub fn get_target(&self) -> Option<SwitchTarget> {
+        let index = self.results.state.selected()?;
+        self.results.items.get(index).cloned()
+    }
+}
+
+//Recently visited places float up, a guild counts as visited through its channels
+fn recency_bonus(target: &SwitchTarget, recent: &[Channel]) -> i64 {
+    let position = match (&target.channel, &target.guild_id) {
+        (Some(channel), _) => recent.iter().position(|visited| visited == channel),
+        (None, Some(guild_id)) => recent
+            .iter()
+            .position(|visited| visited.guild_id == *guild_id),
+        (None, None) => None,
+    };
+
+    match position {
+        Some(v) => 50 - (v as i64).min(50),
+        None => 0,
+    }
+}
+
+//Every query char has to show up in order, None if they don't
+//Consecutive runs and word starts score higher, leftover length costs a bit
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut last_match: Option<usize> = None;
+    let mut position = 0;
+
+    for wanted in query.to_lowercase().chars() {
+        let found = (position..candidate.len()).find(|i| candidate[*i] == wanted)?;
+
+        score += 10;
+        if last_match.is_some() && last_match == found.checked_sub(1) {
+            score += 15;
+        }
+        let word_start = found == 0 || !candidate[found - 1].is_alphanumeric();
+        if word_start {
+            score += 10;
+        }
+        if found == 0 {
+            score += 5;
+        }
+
+        last_match = Some(found);
+        position = found + 1;
+    }
+
+    Some(score - candidate.len() as i64 / 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn channel(id: &str, guild_id: &str) -> Channel {
+        Channel::from(&json!({ "id": id, "name": id, "type": 0, "guild_id": guild_id }))
+    }
+
+    fn target(channel: Option<Channel>, guild_id: Option<&str>) -> SwitchTarget {
+        SwitchTarget {
+            label: String::new(),
+            guild_id: guild_id.map(|v| v.to_string()),
+            channel,
+        }
+    }
+
+    #[test]
+    fn fuzzy_score_needs_every_char_in_order() {
+        assert_eq!(fuzzy_score("", "general"), Some(0));
+        assert!(fuzzy_score("gnrl", "general").is_some());
+        assert!(fuzzy_score("lg", "general").is_none());
+        assert!(fuzzy_score("x", "general").is_none());
+    }
+
+    #[test]
+    fn fuzzy_score_ignores_case() {
+        assert_eq!(fuzzy_score("GEN", "general"), fuzzy_score("gen", "General"));
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_runs_and_word_starts() {
+        let run = fuzzy_score("gen", "#general").unwrap();
+        let scattered = fuzzy_score("gen", "#going-elsewhere-now").unwrap();
+        assert!(run > scattered);
+
+        let word_start = fuzzy_score("ch", "#off-chat").unwrap();
+        let middle = fuzzy_score("ch", "#offchat").unwrap();
+        assert!(word_start > middle);
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_shorter_candidates() {
+        let short = fuzzy_score("dev", "#dev").unwrap();
+        let long = fuzzy_score("dev", "#dev-announcements-archive").unwrap();
+        assert!(short > long);
+    }
+
+    #[test]
+    fn recency_bonus_favors_recent_channels() {
+        let recent = vec![channel("1", "g1"), channel("2", "g2")];
+
+        let newest = recency_bonus(&target(Some(channel("1", "g1")), Some("g1")), &recent);
+        let older = recency_bonus(&target(Some(channel("2", "g2")), Some("g2")), &recent);
+        let never = recency_bonus(&target(Some(channel("3", "g1")), Some("g1")), &recent);
+        assert_eq!(newest, 50);
+        assert_eq!(older, 49);
+        assert_eq!(never, 0);
+    }
+
+    #[test]
+    fn recency_bonus_counts_guilds_through_their_channels() {
+        let recent = vec![channel("1", "g1"), channel("2", "g2")];
+
+        assert_eq!(recency_bonus(&target(None, Some("g2")), &recent), 49);
+        assert_eq!(recency_bonus(&target(None, Some("g3")), &recent), 0);
+        assert_eq!(recency_bonus(&target(None, None), &recent), 0);
+    }
+}
@@ -0,0 +1,96 @@
+//Files going to or from discord, each on its own thread so the ui can keep drawing progress
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+pub const MIB: u64 = 1024 * 1024;
+
+pub struct Transfer<T> {
+    //"Uploading" or "Downloading"
+    verb: &'static str,
+    //"cat.png", or "3 files"
+    pub label: String,
+    pub total: u64,
+    //Bytes so far, the thread counts them up
+    done: Arc<AtomicU64>,
+    result: Receiver<Result<T, String>>,
+}
+
+impl<T: Send + 'static> Transfer<T> {
+    //work gets the byte counter to bump as it goes
+    pub fn spawn<F>(verb: &'static str, label: String, total: u64, work: F) -> Transfer<T>
+    where
+        F: FnOnce(Arc<AtomicU64>) -> Result<T, String> + Send + 'static,
+    {
+        let done = Arc::new(AtomicU64::new(0));
+        let (tx, rx) = mpsc::channel();
+        let thread_done = done.clone();
+        thread::spawn(move || {
+            let _ = tx.send(work(thread_done));
+        });
+
+        Transfer {
+            verb,
+            label,
+            total,
+            done,
+            result: rx,
+        }
+    }
+
+    pub fn progress(&self) -> String {
+        let done = self.done.load(Ordering::Relaxed).min(self.total);
+        let percent = match self.total {
+            0 => 100,
+            total => done * 100 / total,
+        };
+        format!(
+            "{} {} {}% ({}/{})",
+            self.verb,
+            self.label,
+            percent,
+            human_size(done),
+            human_size(self.total)
+        )
+    }
+
+    //None while it's still going
+    pub fn poll(&self) -> Option<Result<T, String>> {
+        match self.result.try_recv() {
+            Ok(v) => Some(v),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                Some(Err(format!("{} {} stopped", self.verb, self.label)))
+            }
+        }
+    }
+}
+
+//Takes the transfer out of its slot once it's done, with its label
+pub fn finished<T: Send + 'static>(
+    slot: &mut Option<Transfer<T>>,
+) -> Option<(String, Result<T, String>)> {
+    let result = slot.as_ref()?.poll()?;
+    let label = slot.take()?.label;
+    Some((label, result))
+}
+
+//The file's name when there's one, a count otherwise
+pub fn label(names: &[String]) -> String {
+    match names {
+        [name] => name.clone(),
+        _ => format!("{} files", names.len()),
+    }
+}
+
+pub fn human_size(bytes: u64) -> String {
+    if bytes >= MIB {
+        format!("{:.1} MB", bytes as f64 / MIB as f64)
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
@@ -0,0 +1,129 @@
+//Sending files, the Transfer shows progress while it goes
+
+use std::path::{Path, PathBuf};
+
+use crate::api::data::{Connection, User};
+use crate::api::wrapper;
+
+use super::transfer::{self, human_size, Transfer, MIB};
+
+pub type Upload = Transfer<()>;
+
+//Checks every file against the limit first, so nothing gets half sent
+pub fn start(
+    conn: &Connection,
+    channel_id: &str,
+    caption: String,
+    files: Vec<PathBuf>,
+    limit: u64,
+) -> Result<Upload, String> {
+    let mut total = 0;
+    for path in &files {
+        let size = std::fs::metadata(path)
+            .map_err(|err| format!("Can't read {}: {}", path.display(), err))?
+            .len();
+        if size > limit {
+            return Err(format!(
+                "{} is {}, over your {} upload limit",
+                file_name(path),
+                human_size(size),
+                human_size(limit)
+            ));
+        }
+        total += size;
+    }
+
+    let names: Vec<String> = files.iter().map(|file| file_name(file)).collect();
+    let conn = conn.clone();
+    let channel_id = channel_id.to_string();
+    Ok(Transfer::spawn(
+        "Uploading",
+        transfer::label(&names),
+        total,
+        move |sent| wrapper::upload_files(&conn, &channel_id, &caption, &files, sent),
+    ))
+}
+
+//Per file, going by the Nitro tier
+pub fn upload_limit(user: &User) -> u64 {
+    match user.premium_type {
+        2 => 500 * MIB,
+        1 | 3 => 50 * MIB,
+        _ => 10 * MIB,
+    }
+}
+
+//Space separated, "quotes" for paths that have spaces in them
+pub fn parse_paths(args: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+
+    for c in args.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    paths.push(expand_home(&std::mem::take(&mut current)));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        paths.push(expand_home(&current));
+    }
+
+    paths
+}
+
+//~/cat.png and friends
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_paths_splits_on_whitespace() {
+        assert_eq!(
+            parse_paths("  a.png\tb.txt   c "),
+            [
+                PathBuf::from("a.png"),
+                PathBuf::from("b.txt"),
+                PathBuf::from("c")
+            ]
+        );
+        assert!(parse_paths("   ").is_empty());
+    }
+
+    #[test]
+    fn parse_paths_keeps_quoted_spaces() {
+        assert_eq!(
+            parse_paths("\"my cat.png\" dog.png"),
+            [PathBuf::from("my cat.png"), PathBuf::from("dog.png")]
+        );
+    }
+
+    #[test]
+    fn parse_paths_expands_home() {
+        let home = match std::env::var_os("HOME") {
+            Some(v) => PathBuf::from(v),
+            None => return,
+        };
+        assert_eq!(parse_paths("~/cat.png"), [home.join("cat.png")]);
+        assert_eq!(parse_paths("a~/cat.png"), [PathBuf::from("a~/cat.png")]);
+    }
+}